@@ -0,0 +1,72 @@
+use std::{cell::OnceCell, rc::Rc};
+
+use cacao::{
+    appkit::{
+        event::{Event, EventMask, EventMonitor},
+        window::{Window, WindowConfig},
+        App, AppDelegate,
+    },
+    notification_center::Dispatcher,
+    view::View,
+};
+
+use crate::{
+    cacao_backend::{key_down, set_app_name},
+    Backend, CacaoBackend, Component, ComponentWrapper, Message, Renderable, RenderableWrapper,
+};
+
+/// Starts the app with `Root` mounted in its main window, returning once the app quits.
+/// The framework provides the app delegate and routes every native event to the component it is for,
+/// so components talk to the rest of the program through `Command`s and `Subscription`s
+pub fn run<Root: Component + Clone + PartialEq + 'static>(props: Root::Props) {
+    let root: Rc<dyn Renderable> = Rc::new(ComponentWrapper::<Root, CacaoBackend>::new(props));
+    let bundle_id = format!("com.cacao-framework.{}", root.name());
+    App::<Runtime, Message>::new(
+        &bundle_id,
+        Runtime {
+            window: Window::new(WindowConfig::default()),
+            content: OnceCell::new(),
+            key_monitor: OnceCell::new(),
+            root,
+        },
+    )
+    .run()
+}
+
+/// The app delegate `run` starts the app with.
+/// Messages dispatched with `App::dispatch_main` end up here and are passed on to the root component
+pub(crate) struct Runtime {
+    root: Rc<dyn Renderable>,
+    window: Window,
+    /// Made once the app has launched, the root renders when it is loaded
+    content: OnceCell<View<RenderableWrapper>>,
+    /// Routes key presses to the focused component for as long as the app runs
+    key_monitor: OnceCell<EventMonitor>,
+}
+
+impl AppDelegate for Runtime {
+    fn did_finish_launching(&self) {
+        // The app menu is there even when no component adds menus of its own
+        set_app_name(self.root.name());
+        CacaoBackend::set_menu_bar(&[]);
+        let content = self
+            .content
+            .get_or_init(|| View::with(RenderableWrapper(Rc::clone(&self.root))));
+        self.window.set_title(self.root.name());
+        self.window.set_content_size(640., 480.);
+        self.window.set_content_view(content);
+        self.window.show();
+        self.key_monitor
+            .get_or_init(|| Event::local_monitor(EventMask::KeyDown, key_down));
+    }
+
+    fn should_terminate_after_last_window_closed(&self) -> bool {
+        true
+    }
+}
+
+impl Dispatcher<Message> for Runtime {
+    fn on_ui_message(&self, message: Message) {
+        self.root.on_message(&message)
+    }
+}
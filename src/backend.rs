@@ -0,0 +1,151 @@
+use std::{
+    any::TypeId,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "macos")]
+use crate::CacaoBackend;
+use crate::{
+    Component, ComponentWrapper, ContainerKind, HeadlessBackend, ListRows, Message, NativeMenu,
+    Renderable, VComponent, VList, WindowAttributes,
+};
+
+/// The backends a nested component can be made with, a new backend has to be added here.
+/// A `VComponent` doesn't know the backend it will render with until its parent creates its node, so it is made with whichever of these that turns out to be
+#[cfg(target_os = "macos")]
+pub type Backends = (HeadlessBackend, CacaoBackend);
+#[cfg(not(target_os = "macos"))]
+pub type Backends = (HeadlessBackend,);
+
+/// Everything the virtual DOM needs from a native UI toolkit.
+/// `ComponentWrapper` works out what changed between renders and only ever touches widgets through these functions, so the same components can be rendered by AppKit on the Mac or by an in-memory backend elsewhere.
+pub trait Backend: Sized + 'static {
+    /// A single native widget created from a `VNode`
+    type Node;
+    /// The native view a component adds its widgets to
+    type Container: 'static;
+    /// A top level window opened for a `VWindow`
+    type Window;
+
+    fn create_container() -> Self::Container;
+    fn create_label(text: &str) -> Self::Node;
+    fn create_button(text: &str) -> Self::Node;
+    fn create_text_input(initial_value: &str) -> Self::Node;
+    fn create_select(options: &[String]) -> Self::Node;
+    /// Creates a list whose rows are created with `rows`, either straight away or as they are needed
+    fn create_list<T>(list: &VList<T>, rows: ListRows<T, Self>) -> Self::Node
+    where
+        T: Component + Clone + PartialEq + 'static;
+    /// Creates the view a nested component renders into.
+    /// The backend is responsible for handing the new container to the component with `Renderable::set_parent_view` and rendering it
+    fn create_custom(component: &VComponent) -> Self::Node;
+    /// Makes a component that renders with this backend, nested components are made with this once their parent creates their node
+    fn mount<T>(props: T::Props) -> Rc<dyn Renderable>
+    where
+        T: Component + Clone + PartialEq + 'static,
+    {
+        Rc::new(ComponentWrapper::<T, Self>::new(props))
+    }
+    /// Creates a node that other nodes can be added to, `as_container` must return its container
+    fn create_group(kind: &ContainerKind) -> Self::Node;
+    /// The view children are added to for nodes made by `create_group`, `None` for any other node
+    fn as_container(node: &Self::Node) -> Option<&Self::Container>;
+
+    /// Sets the text of a label or button, the value of a text input or the title of a box
+    fn set_text(node: &mut Self::Node, text: &str);
+    /// Replaces every option of a select
+    fn set_options(node: &mut Self::Node, options: &[String]);
+    fn set_selected(node: &mut Self::Node, index: Option<usize>);
+    /// Renders every row of a list created by `create_list` again, which happens each time the component that rendered it renders.
+    /// The handlers of the old rows have already been unregistered, every row needs creating again with `rows`
+    fn update_list<T>(node: &mut Self::Node, list: &VList<T>, rows: &ListRows<T, Self>)
+    where
+        T: Component + Clone + PartialEq + 'static;
+    /// Points the nodes native action at the handler registered under `id`, or disconnects it when given `None`.
+    /// Buttons dispatch `Message::click`, text inputs `Message::change` and selects `Message::select`
+    fn set_handler(node: &mut Self::Node, id: Option<usize>);
+
+    /// Gives a node a menu that opens when it is right clicked, replaces the one it has or takes it away when given `None`.
+    /// Choosing an item with an id dispatches `Message::click` with it
+    fn set_context_menu(node: &mut Self::Node, menu: Option<&NativeMenu>);
+    /// Points keys pressed while the node has keyboard focus at the handler registered under `id`, or stops them when given `None`.
+    /// Key presses dispatch `Message::key` with `routing::route_key`, going to the nearest node or container with an id
+    fn set_key_handler(node: &mut Self::Node, id: Option<usize>);
+    /// Points keys pressed in a component that none of its nodes take at the component, see `set_key_handler`
+    fn set_container_key_handler(container: &Self::Container, id: usize);
+    /// Replaces every menu in the menu bar, items dispatch their ids like those of context menus
+    fn set_menu_bar(menus: &[NativeMenu]);
+
+    /// Adds a child in front of the sibling `before`, or after every other child when that is `None`
+    fn insert_child(parent: &Self::Container, child: &Self::Node, before: Option<&Self::Node>);
+    /// Moves an existing child in front of `before`, or to the end.
+    /// The order of the children is the order screen readers move through them in so it has to follow the order they are laid out in
+    fn move_child(parent: &Self::Container, child: &Self::Node, before: Option<&Self::Node>);
+    fn remove_child(parent: &Self::Container, child: &Self::Node);
+    /// Positions the children of a component in the order they were rendered
+    fn apply_layout(parent: &Self::Container, children: &[&Self::Node]);
+
+    /// Opens a window and renders the component into it, handing it a container the same way `create_custom` does.
+    /// When the user closes the window the backend dispatches `Message::close(close)`
+    fn open_window(
+        attributes: &WindowAttributes,
+        content: &VComponent,
+        close: usize,
+    ) -> Self::Window;
+    /// Brings an open window up to date with attributes that differ from `old`
+    fn update_window(window: &mut Self::Window, old: &WindowAttributes, new: &WindowAttributes);
+    /// Closes a window that is no longer wanted, close messages it dispatches after this are ignored
+    fn close_window(window: Self::Window);
+
+    /// Sends a message back into the component tree from a native event
+    fn dispatch(message: Message);
+    /// Arranges for `flush` to be called once the event being handled is done, so changes made while handling it render together
+    fn schedule_flush();
+    /// Arranges for `poll_tasks` to be called on the main thread.
+    /// Unlike everything else here it is called from whichever thread woke a task
+    fn schedule_poll();
+    /// The time timers are measured against
+    fn now() -> Instant;
+    /// Arranges for `fire_timers` to be called once `delay` has passed
+    fn schedule_timer(delay: Duration);
+    /// Has the screen reader read out the text
+    fn announce(text: &str);
+    /// Gives the node keyboard focus, or moves the VoiceOver cursor to it when it can't take keyboard focus
+    fn focus(node: &Self::Node);
+    /// Moves focus to the view of a component the same way as `focus`
+    fn focus_container(container: &Self::Container);
+    /// Whether the node or a widget inside it has keyboard focus
+    fn has_focus(node: &Self::Node) -> bool;
+    fn quit();
+}
+
+/// A tuple of backends, see `Backends`
+pub trait BackendList {
+    /// Makes the component `T` with the backend in the list whose type id is `backend`, `None` when there isn't one
+    fn mount<T>(backend: TypeId, props: &T::Props) -> Option<Rc<dyn Renderable>>
+    where
+        T: Component + Clone + PartialEq + 'static;
+}
+
+macro_rules! backend_list {
+    ($($backend:ident),+) => {
+        impl<$($backend: Backend),+> BackendList for ($($backend,)+) {
+            fn mount<T>(backend: TypeId, props: &T::Props) -> Option<Rc<dyn Renderable>>
+            where
+                T: Component + Clone + PartialEq + 'static,
+            {
+                $(
+                    if backend == TypeId::of::<$backend>() {
+                        return Some($backend::mount::<T>(props.clone()));
+                    }
+                )+
+                None
+            }
+        }
+    };
+}
+
+backend_list!(A);
+backend_list!(A, B);
+backend_list!(A, B, C);
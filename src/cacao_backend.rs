@@ -0,0 +1,755 @@
+use std::{
+    any::type_name,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    ffi::c_void,
+    ptr,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use cacao::{
+    appkit::{
+        event::Event,
+        menu::{Menu, MenuItem},
+        window::{Window, WindowConfig, WindowDelegate},
+        App,
+    },
+    button::Button,
+    foundation::{id, NSInteger, NSString, NSUInteger, BOOL, NO, YES},
+    input::{TextField, TextFieldDelegate},
+    layout::{Layout, LayoutConstraint},
+    listview::ListView,
+    objc::{class, msg_send, runtime::Object},
+    select::Select,
+    text::Label,
+    utils::async_main_thread,
+    view::{View, ViewDelegate},
+};
+
+use crate::{
+    app::Runtime,
+    fire_timers, flush,
+    layout::top_to_bottom,
+    list_view::{AnyListView, MyListView},
+    poll_tasks, route_key, Backend, Component, ComponentWrapper, ContainerKind, KeyPress, ListRows,
+    Message, Modifiers, NativeMenu, NativeMenuItem, Renderable, VComponent, VList,
+    WindowAttributes,
+};
+
+thread_local! {
+    /// The key handler ids of views by their address, looked up from the focused view outwards when a key is pressed
+    static KEY_HANDLERS: RefCell<HashMap<usize, usize>> = RefCell::new(HashMap::new());
+    /// The constraints laying out the children of each container by its address, replaced whenever the children change
+    static LAYOUTS: RefCell<HashMap<usize, Vec<LayoutConstraint>>> = RefCell::new(HashMap::new());
+    /// What the app menu calls the app, set by `run` before the menu bar is first installed
+    static APP_NAME: Cell<&'static str> = const { Cell::new("") };
+}
+
+/// Renders components with AppKit through cacao.
+/// Native events are dispatched with `App::dispatch_main` to the app `run` starts, which routes them to the component they are for
+pub struct CacaoBackend;
+
+impl Backend for CacaoBackend {
+    type Node = CacaoComponent;
+    type Container = View;
+    type Window = CacaoWindow;
+
+    fn create_container() -> Self::Container {
+        View::new()
+    }
+
+    fn create_label(text: &str) -> Self::Node {
+        let label = Label::new();
+        label.set_text(text);
+        CacaoComponent::Label(label)
+    }
+
+    fn create_button(text: &str) -> Self::Node {
+        CacaoComponent::Button(Button::new(text))
+    }
+
+    fn create_text_input(initial_value: &str) -> Self::Node {
+        let input = TextField::with(TextInput::new(None));
+        input.set_text(initial_value);
+        CacaoComponent::TextField(input)
+    }
+
+    fn create_select(options: &[String]) -> Self::Node {
+        let select = Select::new();
+        for option in options {
+            select.add_item(option)
+        }
+        CacaoComponent::Select(select)
+    }
+
+    fn create_list<T>(list: &VList<T>, rows: ListRows<T, Self>) -> Self::Node
+    where
+        T: Component + Clone + PartialEq + 'static,
+    {
+        let list = MyListView::<T>::with(list.count, list.render, rows);
+        CacaoComponent::List(Box::new(list))
+    }
+
+    fn create_custom(component: &VComponent) -> Self::Node {
+        CacaoComponent::View(View::with(RenderableWrapper(Rc::clone(
+            component.renderable(),
+        ))))
+    }
+
+    fn create_group(kind: &ContainerKind) -> Self::Node {
+        let view = View::new();
+        match kind {
+            ContainerKind::Stack => {}
+            ContainerKind::Group => make_accessibility_group(&view),
+            ContainerKind::Box(title) => {
+                make_accessibility_group(&view);
+                set_accessibility_label(&view, title);
+            }
+        }
+        CacaoComponent::Group(view)
+    }
+
+    fn as_container(node: &Self::Node) -> Option<&Self::Container> {
+        match node {
+            CacaoComponent::Group(view) => Some(view),
+            _ => None,
+        }
+    }
+
+    fn set_text(node: &mut Self::Node, text: &str) {
+        match node {
+            CacaoComponent::Label(label) => label.set_text(text),
+            CacaoComponent::Button(button) => button.set_text(text),
+            // Setting the value the user just typed would move their cursor
+            CacaoComponent::TextField(input) if input.get_value() != text => input.set_text(text),
+            CacaoComponent::Group(view) => set_accessibility_label(view, text),
+            _ => {}
+        }
+    }
+
+    fn set_options(node: &mut Self::Node, options: &[String]) {
+        if let CacaoComponent::Select(select) = node {
+            select.remove_all_items();
+            for option in options {
+                select.add_item(option)
+            }
+        }
+    }
+
+    fn set_selected(node: &mut Self::Node, index: Option<usize>) {
+        if let CacaoComponent::Select(select) = node {
+            // Nothing chosen shows the first option, -1 would leave the popup blank
+            select.set_selected_index(index.unwrap_or(0) as NSInteger);
+        }
+    }
+
+    fn update_list<T>(node: &mut Self::Node, list: &VList<T>, _rows: &ListRows<T, Self>)
+    where
+        T: Component + Clone + PartialEq + 'static,
+    {
+        if let CacaoComponent::List(list_view) = node {
+            list_view
+                .as_any_mut()
+                .downcast_mut::<ListView<MyListView<T>>>()
+                .expect("List was rendered by a different component")
+                .delegate
+                .as_mut()
+                .unwrap()
+                .update(list.count, list.render);
+        }
+    }
+
+    fn set_handler(node: &mut Self::Node, id: Option<usize>) {
+        match (node, id) {
+            (CacaoComponent::Button(button), Some(id)) => button
+                .set_action(move |_| App::<Runtime, Message>::dispatch_main(Message::click(id))),
+            (CacaoComponent::Button(button), None) => button.set_action(|_| {}),
+            (CacaoComponent::Select(select), Some(id)) => select.set_action(move |sender| {
+                let index: NSInteger = unsafe { msg_send![sender, indexOfSelectedItem] };
+                App::<Runtime, Message>::dispatch_main(Message::select(id, index as usize))
+            }),
+            (CacaoComponent::Select(select), None) => select.set_action(|_| {}),
+            (CacaoComponent::TextField(input), id) => input.delegate.as_mut().unwrap().id = id,
+            _ => {}
+        }
+    }
+
+    fn set_context_menu(node: &mut Self::Node, menu: Option<&NativeMenu>) {
+        let view = node.as_layout().get_backing_obj();
+        match menu.map(build_menu) {
+            // The view keeps hold of the menu
+            Some(menu) => unsafe {
+                let _: () = msg_send![&*view, setMenu: &*menu.0];
+            },
+            None => unsafe {
+                let _: () = msg_send![&*view, setMenu: std::ptr::null::<Object>()];
+            },
+        }
+    }
+
+    fn set_key_handler(node: &mut Self::Node, id: Option<usize>) {
+        let view = node.as_layout().get_backing_obj();
+        set_view_key_handler(&*view as *const Object as usize, id)
+    }
+
+    fn set_container_key_handler(container: &Self::Container, id: usize) {
+        let view = container.get_backing_obj();
+        set_view_key_handler(&*view as *const Object as usize, Some(id))
+    }
+
+    fn set_menu_bar(menus: &[NativeMenu]) {
+        let mut bar = vec![app_menu()];
+        bar.extend(menus.iter().map(build_menu));
+        App::set_menu(bar)
+    }
+
+    fn insert_child(parent: &Self::Container, child: &Self::Node, before: Option<&Self::Node>) {
+        match before {
+            Some(_) => position_subview(parent, child, before),
+            None => parent.add_subview(child.as_layout()),
+        }
+    }
+
+    fn move_child(parent: &Self::Container, child: &Self::Node, before: Option<&Self::Node>) {
+        // Adding an existing subview again moves it
+        position_subview(parent, child, before)
+    }
+
+    fn remove_child(_parent: &Self::Container, child: &Self::Node) {
+        let view = child.as_layout().get_backing_obj();
+        // Another view could be allocated at the same address
+        set_view_key_handler(&*view as *const Object as usize, None);
+        set_layout(&*view as *const Object as usize, None);
+        child.as_layout().remove_from_superview();
+    }
+
+    fn apply_layout(parent: &Self::Container, children: &[&Self::Node]) {
+        let constraints = top_to_bottom(
+            children.iter().map(|child| child.as_layout()).collect(),
+            &parent.safe_layout_guide,
+            8.,
+        );
+        let view = parent.get_backing_obj();
+        // The old constraints would contradict the new ones once children are reordered
+        if let Some(old) = set_layout(&*view as *const Object as usize, Some(constraints.clone())) {
+            LayoutConstraint::deactivate(&old);
+        }
+        LayoutConstraint::activate(&constraints);
+    }
+
+    fn open_window(
+        attributes: &WindowAttributes,
+        content: &VComponent,
+        close: usize,
+    ) -> Self::Window {
+        let window = Window::with(WindowConfig::default(), WindowEvents { close });
+        window.set_title(&attributes.title);
+        let (width, height) = attributes.size;
+        window.set_content_size(width, height);
+        if let Some((width, height)) = attributes.min_size {
+            window.set_minimum_content_size(width, height);
+        }
+        set_closable(&window, attributes.closable);
+        let content = View::with(RenderableWrapper(Rc::clone(content.renderable())));
+        window.set_content_view(&content);
+        window.show();
+        CacaoWindow {
+            window,
+            _content: content,
+        }
+    }
+
+    fn update_window(window: &mut Self::Window, old: &WindowAttributes, new: &WindowAttributes) {
+        let window = &window.window;
+        if old.title != new.title {
+            window.set_title(&new.title);
+        }
+        // Only resized when asked to so sizes the user picked aren't thrown away
+        if old.size != new.size {
+            let (width, height) = new.size;
+            window.set_content_size(width, height);
+        }
+        if old.min_size != new.min_size {
+            let (width, height) = new.min_size.unwrap_or((0., 0.));
+            window.set_minimum_content_size(width, height);
+        }
+        if old.closable != new.closable {
+            set_closable(window, new.closable);
+        }
+    }
+
+    fn close_window(window: Self::Window) {
+        window.window.close()
+    }
+
+    fn dispatch(message: Message) {
+        App::<Runtime, Message>::dispatch_main(message)
+    }
+
+    fn schedule_flush() {
+        async_main_thread(flush)
+    }
+
+    fn schedule_poll() {
+        async_main_thread(|| {
+            poll_tasks();
+        })
+    }
+
+    fn now() -> Instant {
+        Instant::now()
+    }
+
+    fn schedule_timer(delay: Duration) {
+        extern "C" fn fire(_context: *mut c_void) {
+            fire_timers::<CacaoBackend>()
+        }
+        let delay = delay.as_nanos().try_into().unwrap_or(i64::MAX);
+        unsafe {
+            dispatch_after_f(
+                dispatch_time(DISPATCH_TIME_NOW, delay),
+                &_dispatch_main_q,
+                ptr::null_mut(),
+                fire,
+            )
+        }
+    }
+
+    fn announce(text: &str) {
+        let text = NSString::new(text);
+        unsafe {
+            let app: id = msg_send![class!(NSApplication), sharedApplication];
+            let user_info: id = msg_send![
+                class!(NSDictionary),
+                dictionaryWithObject: &*text
+                forKey: NSAccessibilityAnnouncementKey
+            ];
+            NSAccessibilityPostNotificationWithUserInfo(
+                app,
+                NSAccessibilityAnnouncementRequestedNotification,
+                user_info,
+            );
+        }
+    }
+
+    fn focus(node: &Self::Node) {
+        let view = node.as_layout().get_backing_obj();
+        focus_view(&*view as *const Object as id)
+    }
+
+    fn focus_container(container: &Self::Container) {
+        let view = container.get_backing_obj();
+        focus_view(&*view as *const Object as id)
+    }
+
+    fn has_focus(node: &Self::Node) -> bool {
+        let view = node.as_layout().get_backing_obj();
+        unsafe {
+            let window: id = msg_send![&*view, window];
+            if window.is_null() {
+                return false;
+            }
+            let responder: id = msg_send![window, firstResponder];
+            // The first responder can be the window itself
+            let is_view: BOOL = msg_send![responder, isKindOfClass: class!(NSView)];
+            if is_view == NO {
+                return false;
+            }
+            // Text fields being edited hand first responder to a field editor inside them
+            let inside: BOOL = msg_send![responder, isDescendantOf: &*view];
+            inside == YES
+        }
+    }
+
+    fn quit() {
+        App::terminate()
+    }
+}
+
+#[link(name = "AppKit", kind = "framework")]
+#[allow(non_upper_case_globals)]
+extern "C" {
+    static NSAccessibilityAnnouncementRequestedNotification: id;
+    static NSAccessibilityAnnouncementKey: id;
+    static NSAccessibilityLayoutChangedNotification: id;
+    static NSAccessibilityUIElementsKey: id;
+    fn NSAccessibilityPostNotificationWithUserInfo(element: id, notification: id, user_info: id);
+}
+
+/// A dispatch queue, only ever handled by reference
+#[repr(C)]
+struct DispatchQueue {
+    _private: [u8; 0],
+}
+
+const DISPATCH_TIME_NOW: u64 = 0;
+
+// libdispatch is part of libSystem so it is always linked
+#[allow(non_upper_case_globals)]
+extern "C" {
+    /// The queue `dispatch_get_main_queue` returns, which runs work on the main thread
+    static _dispatch_main_q: DispatchQueue;
+    fn dispatch_time(when: u64, delta: i64) -> u64;
+    fn dispatch_after_f(
+        when: u64,
+        queue: *const DispatchQueue,
+        context: *mut c_void,
+        work: extern "C" fn(*mut c_void),
+    );
+}
+
+/// Makes the view first responder, or has VoiceOver move to it when it won't accept it, such as a label or a group
+fn focus_view(view: id) {
+    unsafe {
+        let window: id = msg_send![view, window];
+        // Views that haven't been added to a window yet can't take focus
+        if window.is_null() {
+            return;
+        }
+        let focused: BOOL = msg_send![window, makeFirstResponder: view];
+        if focused == YES {
+            return;
+        }
+        let elements: id = msg_send![class!(NSArray), arrayWithObject: view];
+        let user_info: id = msg_send![
+            class!(NSDictionary),
+            dictionaryWithObject: elements
+            forKey: NSAccessibilityUIElementsKey
+        ];
+        NSAccessibilityPostNotificationWithUserInfo(
+            window,
+            NSAccessibilityLayoutChangedNotification,
+            user_info,
+        );
+    }
+}
+
+/// Replaces the constraints kept for a container, returning the ones it had
+fn set_layout(
+    view: usize,
+    constraints: Option<Vec<LayoutConstraint>>,
+) -> Option<Vec<LayoutConstraint>> {
+    LAYOUTS.with(|layouts| match constraints {
+        Some(constraints) => layouts.borrow_mut().insert(view, constraints),
+        None => layouts.borrow_mut().remove(&view),
+    })
+}
+
+fn set_view_key_handler(view: usize, id: Option<usize>) {
+    KEY_HANDLERS.with(|handlers| match id {
+        Some(id) => handlers.borrow_mut().insert(view, id),
+        None => handlers.borrow_mut().remove(&view),
+    });
+}
+
+/// Watches the key presses of the app, installed by `run`.
+/// Each one is routed to the nearest view with a key handler, starting from the first responder of the key window and going up its superviews.
+/// Key presses that are handled are swallowed, the rest go on to AppKit so text fields still type them
+pub(crate) fn key_down(event: Event) -> Option<Event> {
+    let mut view: id = unsafe {
+        let app: id = msg_send![class!(NSApplication), sharedApplication];
+        let window: id = msg_send![app, keyWindow];
+        if window.is_null() {
+            return Some(event);
+        }
+        msg_send![window, firstResponder]
+    };
+    while !view.is_null() {
+        // The first responder can be the window itself
+        let is_view: BOOL = unsafe { msg_send![view, isKindOfClass: class!(NSView)] };
+        if is_view == NO {
+            break;
+        }
+        let handler =
+            KEY_HANDLERS.with(|handlers| handlers.borrow().get(&(view as usize)).copied());
+        if let Some(handler) = handler {
+            let handled = route_key(&Message::key(handler, key_press(&event)));
+            return if handled { None } else { Some(event) };
+        }
+        view = unsafe { msg_send![view, superview] };
+    }
+    Some(event)
+}
+
+fn key_press(event: &Event) -> KeyPress {
+    // NSEventModifierFlagShift, Control, Option and Command
+    const SHIFT: NSUInteger = 1 << 17;
+    const CONTROL: NSUInteger = 1 << 18;
+    const OPTION: NSUInteger = 1 << 19;
+    const COMMAND: NSUInteger = 1 << 20;
+    let (key_code, flags, ignoring_modifiers, unshifted): (u16, NSUInteger, id, id) = unsafe {
+        (
+            msg_send![&*event.0, keyCode],
+            msg_send![&*event.0, modifierFlags],
+            msg_send![&*event.0, charactersIgnoringModifiers],
+            msg_send![&*event.0, charactersByApplyingModifiers: 0 as NSUInteger],
+        )
+    };
+    KeyPress {
+        key_code,
+        characters: event.characters(),
+        characters_ignoring_modifiers: NSString::retain(ignoring_modifiers).to_string(),
+        unshifted_characters: NSString::retain(unshifted).to_string(),
+        modifiers: Modifiers {
+            command: flags & COMMAND != 0,
+            shift: flags & SHIFT != 0,
+            option: flags & OPTION != 0,
+            control: flags & CONTROL != 0,
+        },
+    }
+}
+
+/// Sets the name the app menu uses for the app
+pub(crate) fn set_app_name(name: &'static str) {
+    APP_NAME.with(|app_name| app_name.set(name))
+}
+
+/// The standard menu AppKit shows first in the menu bar, titled with the name of the app
+fn app_menu() -> Menu {
+    Menu::new(
+        "",
+        vec![
+            MenuItem::About(APP_NAME.with(Cell::get).to_string()),
+            MenuItem::Separator,
+            MenuItem::Services,
+            MenuItem::Separator,
+            MenuItem::Hide,
+            MenuItem::HideOthers,
+            MenuItem::ShowAll,
+            MenuItem::Separator,
+            MenuItem::Quit,
+        ],
+    )
+}
+
+/// Builds a menu whose items dispatch their ids when chosen
+fn build_menu(menu: &NativeMenu) -> Menu {
+    let built = Menu::new(
+        &menu.title,
+        menu.items.iter().map(build_menu_item).collect(),
+    );
+    // Otherwise AppKit decides which items are enabled itself
+    let _: () = unsafe { msg_send![&*built.0, setAutoenablesItems: NO] };
+    built
+}
+
+fn build_menu_item(item: &NativeMenuItem) -> MenuItem {
+    let mut built = MenuItem::new(&item.title);
+    if let Some(key) = &item.key_equivalent {
+        built = built.key(key);
+    }
+    if let Some(id) = item.id {
+        built = built.action(move || App::<Runtime, Message>::dispatch_main(Message::click(id)));
+    }
+    if let MenuItem::Custom(native) = &built {
+        let enabled = if item.enabled { YES } else { NO };
+        let state: NSInteger = if item.checked { 1 } else { 0 };
+        unsafe {
+            let _: () = msg_send![&**native, setEnabled: enabled];
+            let _: () = msg_send![&**native, setState: state];
+        }
+    }
+    built
+}
+
+/// AppKit has no setter for whether a window has a close button so the bit for it in the style mask is flipped directly
+fn set_closable<T>(window: &Window<T>, closable: bool) {
+    const CLOSABLE: NSUInteger = 1 << 1;
+    unsafe {
+        let mask: NSUInteger = msg_send![&*window.objc, styleMask];
+        let mask = if closable {
+            mask | CLOSABLE
+        } else {
+            mask & !CLOSABLE
+        };
+        let _: () = msg_send![&*window.objc, setStyleMask: mask];
+    }
+}
+
+/// Puts a view in front of `before` in the parents subviews, or at the end without one.
+/// VoiceOver walks subviews in this order so it needs to match the order they are laid out in
+fn position_subview(parent: &View, child: &CacaoComponent, before: Option<&CacaoComponent>) {
+    let parent = parent.get_backing_obj();
+    let child = child.as_layout().get_backing_obj();
+    let before = before.map(|before| before.as_layout().get_backing_obj());
+    let (ordering, relative_to): (NSInteger, *const Object) = match &before {
+        // NSWindowBelow
+        Some(before) => (-1, &**before),
+        // NSWindowAbove relative to nothing puts it on top of every other subview
+        None => (1, std::ptr::null()),
+    };
+    let _: () = unsafe {
+        msg_send![&*parent, addSubview: &*child positioned: ordering relativeTo: relative_to]
+    };
+}
+
+/// Makes VoiceOver announce the view as a single group that can be interacted with to reach its children
+fn make_accessibility_group(view: &View) {
+    let view = view.get_backing_obj();
+    let role = NSString::new("AXGroup");
+    unsafe {
+        let _: () = msg_send![&*view, setAccessibilityElement: YES];
+        let _: () = msg_send![&*view, setAccessibilityRole: &*role];
+    }
+}
+
+fn set_accessibility_label(view: &View, label: &str) {
+    let view = view.get_backing_obj();
+    let label = NSString::new(label);
+    let _: () = unsafe { msg_send![&*view, setAccessibilityLabel: &*label] };
+}
+
+/// The delegate of the view a component renders into
+#[derive(Clone)]
+pub struct RenderableWrapper(pub(crate) Rc<dyn Renderable>);
+
+impl ViewDelegate for RenderableWrapper {
+    const NAME: &'static str = "custom_component";
+    fn did_load(&mut self, view: View) {
+        self.0.set_parent_view(Box::new(view));
+        self.0.render()
+    }
+}
+
+impl<T> ViewDelegate for ComponentWrapper<T, CacaoBackend>
+where
+    T: Component + Clone + PartialEq + 'static,
+{
+    const NAME: &'static str = "ignored";
+    fn subclass_name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+    fn did_load(&mut self, view: View) {
+        self.set_parent_view(Box::new(view));
+        self.render();
+    }
+}
+
+pub enum CacaoComponent {
+    Label(Label),
+    Button(Button),
+    View(View<RenderableWrapper>),
+    TextField(TextField<TextInput>),
+    /// The list delegate is generic over the component that rendered it so it is boxed up here
+    List(Box<dyn AnyListView>),
+    Select(Select),
+    /// A stack, group or box holding other nodes
+    Group(View),
+}
+
+impl CacaoComponent {
+    pub fn as_label(&self) -> Option<&Label> {
+        if let Self::Label(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_label_mut(&mut self) -> Option<&mut Label> {
+        if let Self::Label(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_button(&self) -> Option<&Button> {
+        if let Self::Button(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_button_mut(&mut self) -> Option<&mut Button> {
+        if let Self::Button(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_view(&self) -> Option<&View<RenderableWrapper>> {
+        if let Self::View(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_view_mut(&mut self) -> Option<&mut View<RenderableWrapper>> {
+        if let Self::View(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_layout(&self) -> &dyn Layout {
+        match self {
+            CacaoComponent::Label(label) => label,
+            CacaoComponent::Button(button) => button,
+            CacaoComponent::View(view) => view,
+            CacaoComponent::TextField(text_input) => text_input,
+            CacaoComponent::List(list) => list.as_layout(),
+            CacaoComponent::Select(select) => select,
+            CacaoComponent::Group(view) => view,
+        }
+    }
+
+    pub fn as_text_field(&self) -> Option<&TextField<TextInput>> {
+        if let Self::TextField(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_text_field_mut(&mut self) -> Option<&mut TextField<TextInput>> {
+        if let Self::TextField(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct TextInput {
+    id: Option<usize>,
+}
+
+impl TextInput {
+    pub fn new(id: Option<usize>) -> Self {
+        Self { id }
+    }
+}
+
+impl TextFieldDelegate for TextInput {
+    const NAME: &'static str = "TextInput";
+    fn text_did_change(&self, value: &str) {
+        if let Some(id) = self.id {
+            App::<Runtime, Message>::dispatch_main(Message::change(id, value.to_owned()));
+        }
+    }
+}
+
+/// A window opened for a `VWindow`
+pub struct CacaoWindow {
+    window: Window<WindowEvents>,
+    /// The view the content component renders into, kept alive as long as the window
+    _content: View<RenderableWrapper>,
+}
+
+/// Dispatches the close message of a window when it closes
+pub struct WindowEvents {
+    close: usize,
+}
+
+impl WindowDelegate for WindowEvents {
+    const NAME: &'static str = "FrameworkWindow";
+    fn will_close(&self) {
+        App::<Runtime, Message>::dispatch_main(Message::close(self.close));
+    }
+}
@@ -0,0 +1,181 @@
+use std::{fmt::Debug, future::Future, hash::Hash, pin::Pin};
+
+use crate::KeyValue;
+
+/// Side effects a handler or `Component::on_message` asks for, run once the state it changed has been committed.
+/// Anything that produces a result hands it back to the component as a `Component::Message`
+pub struct Command<M>(pub(crate) Vec<Effect<M>>);
+
+pub(crate) enum Effect<M> {
+    Perform(Box<dyn FnOnce() -> M>),
+    Spawn(Pin<Box<dyn Future<Output = M>>>),
+    Announce(String),
+    /// The key of the node to focus
+    Focus(KeyValue),
+    /// The keys leading through containers to the node to focus
+    FocusPath(Vec<KeyValue>),
+    Quit,
+}
+
+impl<M> Command<M> {
+    /// Does nothing
+    pub fn none() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Runs each of the commands in order
+    pub fn batch(commands: impl IntoIterator<Item = Command<M>>) -> Self {
+        Self(commands.into_iter().flat_map(|command| command.0).collect())
+    }
+
+    /// Calls the closure and sends what it returns to the component
+    pub fn perform(perform: impl FnOnce() -> M + 'static) -> Self {
+        Self(vec![Effect::Perform(Box::new(perform))])
+    }
+
+    /// Runs the future on the main thread and sends what it resolves to to the component once mapped with `map`.
+    /// The future is dropped if the component is unmounted before it finishes
+    pub fn spawn<F: Future + 'static>(future: F, map: impl FnOnce(F::Output) -> M + 'static) -> Self
+    where
+        M: 'static,
+    {
+        Self(vec![Effect::Spawn(Box::pin(
+            async move { map(future.await) },
+        ))])
+    }
+
+    /// Has VoiceOver read out the text once the component has rendered
+    pub fn announce(text: impl Into<String>) -> Self {
+        Self(vec![Effect::Announce(text.into())])
+    }
+
+    /// Moves keyboard focus to the node rendered with this key once the component has rendered, see `VNode::key`.
+    /// Nodes in containers are found as well, keys of nodes outside of containers are checked first
+    pub fn focus(key: impl Hash + Eq + Debug + 'static) -> Self {
+        Self(vec![Effect::Focus(KeyValue::new(key))])
+    }
+
+    /// Moves keyboard focus to the node reached by following the keys from the nodes the component renders through the containers they name.
+    /// For when the same key is used in more than one container
+    pub fn focus_path(path: impl IntoIterator<Item = KeyValue>) -> Self {
+        Self(vec![Effect::FocusPath(path.into_iter().collect())])
+    }
+
+    /// Quits the app
+    pub fn quit() -> Self {
+        Self(vec![Effect::Quit])
+    }
+}
+
+impl<M> Default for Command<M> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// What a handler or `Component::on_message` can return.
+/// `()` always renders again and `bool` renders when it is true, a `Command` renders again and runs the command, or both can be given as a pair
+pub trait IntoUpdate<M> {
+    /// Whether to render again and what to run afterwards
+    fn into_update(self) -> (bool, Command<M>);
+}
+
+impl<M> IntoUpdate<M> for () {
+    fn into_update(self) -> (bool, Command<M>) {
+        (true, Command::none())
+    }
+}
+
+impl<M> IntoUpdate<M> for bool {
+    fn into_update(self) -> (bool, Command<M>) {
+        (self, Command::none())
+    }
+}
+
+impl<M> IntoUpdate<M> for Command<M> {
+    fn into_update(self) -> (bool, Command<M>) {
+        (true, self)
+    }
+}
+
+impl<M> IntoUpdate<M> for (bool, Command<M>) {
+    fn into_update(self) -> (bool, Command<M>) {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClickHandler, Command, Component, HeadlessBackend, HeadlessEvent, TestHarness, VButton,
+        VLabel, VNode,
+    };
+
+    #[derive(PartialEq, Clone)]
+    struct Saver;
+
+    #[derive(PartialEq, Clone)]
+    enum SaverMessage {
+        Saved,
+    }
+
+    impl Component for Saver {
+        type Props = ();
+        type State = bool;
+        type Message = SaverMessage;
+        type Update = (bool, Command<SaverMessage>);
+        fn render(_props: &Self::Props, saved: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, _| {
+                        Command::batch([
+                            Command::announce("Saving"),
+                            Command::perform(|| SaverMessage::Saved),
+                        ])
+                    })),
+                    text: "Save".to_string(),
+                }),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, _| Command::quit())),
+                    text: "Quit".to_string(),
+                }),
+                VNode::group(vec![VNode::Label(VLabel {
+                    text: if *saved { "Saved" } else { "Not saved" }.to_string(),
+                })
+                .key("status")]),
+            ]
+        }
+        fn on_message(
+            SaverMessage::Saved: &SaverMessage,
+            _props: &Self::Props,
+            saved: &mut Self::State,
+        ) -> Self::Update {
+            *saved = true;
+            (true, Command::focus("status"))
+        }
+    }
+
+    #[test]
+    fn runs_commands_after_rendering() {
+        let harness = TestHarness::<Saver>::new(());
+        HeadlessBackend::take_events();
+        harness.click(&harness.find_button("Save"));
+        let status = harness.find_label_by_text("Saved").id();
+        let events = HeadlessBackend::take_events();
+        let effects = &events[events.len() - 2..];
+        assert_eq!(
+            effects,
+            [
+                HeadlessEvent::Announce {
+                    text: "Saving".to_string()
+                },
+                HeadlessEvent::Focus { widget: status }
+            ]
+        );
+        harness.click(&harness.find_button("Quit"));
+        assert_eq!(
+            HeadlessBackend::take_events().last(),
+            Some(&HeadlessEvent::Quit)
+        );
+    }
+}
@@ -1,285 +1,1130 @@
 use std::{
     any::{type_name, Any, TypeId},
-    cell::RefCell,
+    cell::{Cell, OnceCell, RefCell},
     collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
     marker::PhantomData,
-    rc::Rc,
+    rc::{Rc, Weak},
     sync::atomic,
 };
 
-use cacao::{
-    appkit::{App, AppDelegate},
-    button::Button,
-    foundation::NSInteger,
-    input::{TextField, TextFieldDelegate},
-    layout::{Layout, LayoutConstraint},
-    listview::ListView,
-    notification_center::Dispatcher,
-    objc::msg_send,
-    select::Select,
-    text::Label,
-    view::{View, ViewDelegate},
+use crate::{
+    assign_keys,
+    command::Effect,
+    executor::{cancel, is_running, spawn},
+    handler::rendering,
+    menu::{set_menu_bar, NativeMenu, NativeMenuItem},
+    reconcile, route,
+    routing::{add_route, hold_events, key_handled, remove_route},
+    scheduler::{after_flush, schedule, Scheduled},
+    snapshot::{short_type_name, write_node},
+    Backend, BackendList, Backends, ChangeHandler, Chord, ClickHandler, Command, IntoUpdate, Key,
+    KeyDownHandler, KeyPress, KeyValue, Patch, SelectHandler, Subscription, VMenu, VWindow,
 };
 
-use crate::{layout::top_to_bottom, list_view::MyListView};
+/// A mounted instance of a component.
+/// Clones are handles to the same instance
+pub struct ComponentWrapper<T: Component + PartialEq, B: Backend>(Rc<Instance<T, B>>);
 
-pub struct ComponentWrapper<T: Component + PartialEq, D: Dispatcher<Message> + AppDelegate> {
+impl<T: Component + PartialEq, B: Backend> Clone for ComponentWrapper<T, B> {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+struct Instance<T: Component + PartialEq, B: Backend> {
     props: Rc<RefCell<T::Props>>,
     state: Rc<RefCell<T::State>>,
-    click_handlers: Rc<RefCell<HashMap<usize, ClickHandler<T>>>>,
-    change_handlers: Rc<RefCell<HashMap<usize, ChangeHandler<T>>>>,
-    select_handlers: Rc<RefCell<HashMap<usize, SelectHandler<T>>>>,
-    parent_view: RefCell<View>,
-    sub_views: Rc<RefCell<HashMap<usize, CacaoComponent<T, D>>>>,
-    vdom: Rc<RefCell<HashMap<usize, VNode<T>>>>,
+    click_handlers: Handlers<ClickHandler<T>>,
+    change_handlers: Handlers<ChangeHandler<T>>,
+    select_handlers: Handlers<SelectHandler<T>>,
+    key_handlers: Handlers<KeyDownHandler<T>>,
+    /// Set once the component has been given somewhere to render to
+    parent_view: Rc<RefCell<Option<B::Container>>>,
+    sub_views: Rc<RefCell<SubViews<B>>>,
+    /// The nodes from the last render in the order they were rendered
+    vdom: Rc<RefCell<VDom<T>>>,
+    /// Delivers the messages of the `Callback`s the component makes
+    receiver: Rc<dyn Fn(T::Message)>,
+    /// Looks key presses up in the components shortcuts, components nested in this one pass those they don't handle on to it
+    keys: Rc<dyn Fn(&KeyPress)>,
+    /// Where key presses this component doesn't handle go
+    key_parent: RefCell<Option<KeyParent>>,
+    /// The id key presses in the components view are dispatched with once it is mounted
+    key_route: Cell<Option<usize>>,
+    /// Set when the component needs to render on the next `flush`
+    dirty: Cell<bool>,
+    /// How many components this one is nested in
+    depth: Cell<usize>,
+    /// The props and state of the last render, `None` until the component has been mounted
+    rendered: RefCell<Option<(T::Props, T::State)>>,
+    /// The ids of the tasks spawned by the component's commands, cancelled when it is unmounted
+    tasks: RefCell<Vec<usize>>,
+    /// What `Component::subscriptions` returned after the last render, each with what stops it when dropped
+    subscriptions: RefCell<Subscribed<T::Message>>,
+    /// What `Component::windows` returned after the last render along with the windows opened for them
+    windows: RefCell<Vec<OpenWindow<T, B>>>,
+    /// What `Component::menu_bar` returned after the last render along with the ids of their items handlers
+    menu_bar: RefCell<Vec<(VMenu<T>, MenuIds)>>,
     component: PhantomData<T>,
-    app: PhantomData<D>,
 }
 
-pub trait Component {
-    type Props: Clone + PartialEq;
-    type State: Clone + PartialEq + Default;
-    type Message: Clone + PartialEq = ();
-    fn render(props: &Self::Props, state: &Self::State) -> Vec<(usize, VNode<Self>)>;
-    fn on_message(_msg: &Self::Message, _props: &Self::Props, _state: &mut Self::State) -> bool {
-        false
+type VDom<T> = Vec<(Key, VNode<T>)>;
+
+type Handlers<H> = Rc<RefCell<HashMap<usize, H>>>;
+
+type Subscribed<M> = Vec<(Subscription<M>, Box<dyn Any>)>;
+
+/// The id each item of a menu dispatches and the registration of its handler, for items that have one
+type MenuIds = Vec<Option<(usize, Registration)>>;
+
+/// Hands key presses a component doesn't handle to the component it is nested in
+pub type KeyParent = Weak<dyn Fn(&KeyPress)>;
+
+struct OpenWindow<T: Component, B: Backend> {
+    window: VWindow<T>,
+    native: B::Window,
+    /// Unroutes the windows close message
+    _close: Registration,
+}
+
+/// The widgets created for a list of sibling nodes, along with those created for the children of any containers among them.
+/// Dropping a node's entries unregisters its handlers
+struct SubViews<B: Backend> {
+    nodes: HashMap<Key, B::Node>,
+    nested: HashMap<Key, SubViews<B>>,
+    registrations: HashMap<Key, Registration>,
+    /// The ids of the items of context menus
+    menus: HashMap<Key, MenuIds>,
+    key_registrations: HashMap<Key, Registration>,
+    /// The widgets of the rows of lists, shared with the list so it can create rows as they are needed
+    lists: HashMap<Key, Rc<RefCell<SubViews<B>>>>,
+    /// A node created with `VNode::autofocus` since the last render
+    autofocus: Option<Key>,
+}
+
+impl<B: Backend> SubViews<B> {
+    /// The keys leading through containers to the widget rendered for a key, looking in containers if none of these nodes have it
+    fn path_to(&self, key: &Key) -> Option<Vec<Key>> {
+        if self.nodes.contains_key(key) {
+            return Some(vec![key.clone()]);
+        }
+        self.nested.iter().find_map(|(outer, nested)| {
+            let mut path = nested.path_to(key)?;
+            path.insert(0, outer.clone());
+            Some(path)
+        })
+    }
+
+    /// The widget at the end of a path of keys through containers
+    fn find_path(&self, path: &[Key]) -> Option<&B::Node> {
+        match path {
+            [] => None,
+            [key] => self.nodes.get(key),
+            [outer, rest @ ..] => self.nested.get(outer)?.find_path(rest),
+        }
+    }
+
+    /// The keys leading to the innermost widget that has or holds keyboard focus, if any of these do
+    fn focused_path(&self) -> Option<Vec<Key>> {
+        let (key, _) = self.nodes.iter().find(|(_, node)| B::has_focus(node))?;
+        let mut path = vec![key.clone()];
+        if let Some(inner) = self.nested.get(key).and_then(SubViews::focused_path) {
+            path.extend(inner)
+        }
+        Some(path)
+    }
+
+    /// Forgets every node created with `VNode::autofocus` since the last render, returning the path to one of them
+    fn take_autofocus(&mut self) -> Option<Vec<Key>> {
+        let own = self.autofocus.take().map(|key| vec![key]);
+        // Every container is visited so none of them are left holding one
+        let nested = self
+            .nested
+            .iter_mut()
+            .filter_map(|(outer, nested)| {
+                let mut path = nested.take_autofocus()?;
+                path.insert(0, outer.clone());
+                Some(path)
+            })
+            .collect::<Vec<_>>();
+        own.or_else(|| nested.into_iter().next())
+    }
+}
+
+impl<B: Backend> Default for SubViews<B> {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            nested: HashMap::new(),
+            registrations: HashMap::new(),
+            menus: HashMap::new(),
+            key_registrations: HashMap::new(),
+            lists: HashMap::new(),
+            autofocus: None,
+        }
+    }
+}
+
+/// Removes a handler and its route from the component it was registered with when dropped
+struct Registration(Option<Box<dyn FnOnce()>>);
+
+impl Registration {
+    fn new<H: 'static>(handlers: &Handlers<H>, id: usize) -> Self {
+        let handlers = Rc::downgrade(handlers);
+        Self(Some(Box::new(move || {
+            if let Some(handlers) = handlers.upgrade() {
+                handlers.borrow_mut().remove(&id);
+            }
+            remove_route(id);
+        })))
     }
 }
 
-impl ViewDelegate for RenderableWrapper {
-    const NAME: &'static str = "custom_component";
-    fn did_load(&mut self, view: cacao::view::View) {
-        self.0.clone().set_parent_view(view);
-        self.0.render()
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(unregister) = self.0.take() {
+            unregister()
+        }
     }
 }
 
-impl<T, D> ViewDelegate for ComponentWrapper<T, D>
+/// Creates the rows of a list with the component that rendered it, so the handlers in them go to that component.
+/// The handlers of a row stay registered until it is created again, the list is updated or the list is removed
+pub struct ListRows<T: Component + PartialEq, B: Backend> {
+    instance: Weak<Instance<T, B>>,
+    rows: Rc<RefCell<SubViews<B>>>,
+}
+
+impl<T: Component + PartialEq, B: Backend> Clone for ListRows<T, B> {
+    fn clone(&self) -> Self {
+        Self {
+            instance: self.instance.clone(),
+            rows: Rc::clone(&self.rows),
+        }
+    }
+}
+
+impl<T, B> ListRows<T, B>
 where
-    T: Component + Clone + PartialEq + 'static,
-    D: Dispatcher<Message> + AppDelegate + 'static,
+    T: Component + PartialEq + Clone + 'static,
+    B: Backend,
 {
-    const NAME: &'static str = "ignored";
-    fn subclass_name(&self) -> &'static str {
-        type_name::<Self>()
+    /// Renders the row at `index` and creates a widget for each of its nodes
+    pub fn create_row(&self, render: ListRender<T>, index: usize) -> Vec<B::Node> {
+        let Some(instance) = self.instance.upgrade() else {
+            return Vec::new();
+        };
+        let component = ComponentWrapper(instance);
+        let mut vdom = render(
+            index,
+            &component.0.props.borrow(),
+            &component.0.state.borrow(),
+        );
+        let mut row = SubViews::default();
+        let nodes = vdom
+            .iter_mut()
+            .enumerate()
+            .map(|(position, node)| component.create_node(&Key::Position(position), node, &mut row))
+            .collect();
+        self.rows
+            .borrow_mut()
+            .nested
+            .insert(Key::Position(index), row);
+        nodes
+    }
+
+    /// Unregisters the handlers of every row
+    fn clear(&self) {
+        self.rows.borrow_mut().nested.clear()
+    }
+}
+
+pub trait Component {
+    type Props: Clone + PartialEq;
+    type State: Clone + PartialEq + Default;
+    type Message: Clone + PartialEq = ();
+    /// What `on_message` returns, set it to `Command<Self::Message>` or `(bool, Command<Self::Message>)` to run commands
+    type Update: IntoUpdate<Self::Message> + Default = bool;
+    /// Nodes are told apart between renders by their position unless they are given a key with `VNode::key`
+    fn render(props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>>;
+    fn on_message(
+        _msg: &Self::Message,
+        _props: &Self::Props,
+        _state: &mut Self::State,
+    ) -> Self::Update {
+        Self::Update::default()
+    }
+    /// Called once the component has rendered into its view for the first time, after any components nested in it
+    fn mounted(_props: &Self::Props, _state: &Self::State) {}
+    /// Called after each later render once its patches have been applied
+    fn updated(
+        _old_props: &Self::Props,
+        _old_state: &Self::State,
+        _props: &Self::Props,
+        _state: &Self::State,
+    ) {
+    }
+    /// Called before the component is removed or replaced, before any components nested in it
+    fn will_unmount(_props: &Self::Props, _state: &Self::State) {}
+    /// What the component listens to given its current props and state, checked after every render
+    fn subscriptions(
+        _props: &Self::Props,
+        _state: &Self::State,
+    ) -> Vec<Subscription<Self::Message>> {
+        Vec::new()
+    }
+    /// The windows the component keeps open given its current props and state, checked after every render
+    fn windows(_props: &Self::Props, _state: &Self::State) -> Vec<VWindow<Self>> {
+        Vec::new()
+    }
+    /// The menus the component adds to the menu bar given its current props and state, checked after every render.
+    /// Menus from every mounted component are shown, in the order the components first added any
+    fn menu_bar(_props: &Self::Props, _state: &Self::State) -> Vec<VMenu<Self>> {
+        Vec::new()
     }
-    fn did_load(&mut self, view: cacao::view::View) {
-        self.parent_view = RefCell::new(view);
-        self.render();
+    /// Messages to send the component when a key is pressed in it, checked after any `VNode::on_key_down` handler of the focused node passes on the key press.
+    /// Key presses no shortcut matches go on to the component this one is nested in
+    fn shortcuts(_props: &Self::Props, _state: &Self::State) -> Vec<(Chord, Self::Message)> {
+        Vec::new()
     }
 }
 
 // The clone and PartialEq requirements here are needed by the compiler despite never being called on S as parts of the virtual DOM do get cloned
-impl<T, D> ComponentWrapper<T, D>
+impl<T, B> ComponentWrapper<T, B>
 where
     T: Component + PartialEq + Clone + 'static,
-    D: Dispatcher<Message> + AppDelegate + 'static,
+    B: Backend,
 {
     pub fn new(props: T::Props) -> Self {
-        Self {
-            parent_view: RefCell::new(View::new()),
-            sub_views: Rc::default(),
-            props: Rc::new(RefCell::new(props)),
-            state: Rc::default(),
-            click_handlers: Rc::default(),
-            change_handlers: Default::default(),
-            select_handlers: Default::default(),
-            vdom: Rc::default(),
-            component: PhantomData,
-            app: PhantomData,
-        }
+        Self(Rc::new_cyclic(|instance: &Weak<Instance<T, B>>| {
+            let keys_instance = instance.clone();
+            let instance = instance.clone();
+            Instance {
+                parent_view: Rc::default(),
+                sub_views: Rc::default(),
+                props: Rc::new(RefCell::new(props)),
+                state: Rc::default(),
+                click_handlers: Rc::default(),
+                change_handlers: Default::default(),
+                select_handlers: Default::default(),
+                key_handlers: Default::default(),
+                vdom: Rc::default(),
+                receiver: Rc::new(move |message| {
+                    if let Some(instance) = instance.upgrade() {
+                        ComponentWrapper(instance).receive(message)
+                    }
+                }),
+                keys: Rc::new(move |press| {
+                    if let Some(instance) = keys_instance.upgrade() {
+                        ComponentWrapper(instance).key_down(press)
+                    }
+                }),
+                key_parent: RefCell::new(None),
+                key_route: Cell::new(None),
+                dirty: Cell::new(false),
+                depth: Cell::new(0),
+                rendered: RefCell::new(None),
+                tasks: RefCell::default(),
+                subscriptions: RefCell::default(),
+                windows: RefCell::default(),
+                menu_bar: RefCell::default(),
+                component: PhantomData,
+            }
+        }))
     }
 
-    /// Call this to let your component register button clicks
+    /// Delivers a message to the component it is meant for.
+    /// Messages from native widgets go straight to the component that rendered the widget with `route`, custom messages are broadcast to this component and every component nested in it
     pub fn on_message(&self, message: &Message) {
         match &message.payload {
-            Payload::Click => {
-                if let Some(handler) = self.click_handlers.borrow_mut().get_mut(&message.id) {
-                    handler(&*self.props.borrow(), &mut *self.state.borrow_mut());
-                }
-                // We need this check in a separate block to ensure the borrow of handler is dropped
-                if self.has_handler_for(&message.id) {
-                    self.render()
-                } else {
-                    for (_, comp) in self.vdom.borrow().iter() {
-                        if let VNode::Custom(comp) = comp {
-                            comp.renderable.0.on_message(message)
+            Payload::Custom(_) => self.deliver_custom(message),
+            _ => {
+                route(message);
+            }
+        }
+    }
+
+    /// Sends a custom message to this component and every component nested in it.
+    /// Components whose `Component::Message` is a different type ignore it
+    pub fn broadcast(&self, message: impl Any + Send + Sync) {
+        self.deliver_custom(&Message::custom(message))
+    }
+
+    fn deliver_custom(&self, message: &Message) {
+        let Payload::Custom(inner_message) = &message.payload else {
+            return;
+        };
+        self.forward_to_children(message);
+        if let Some(message) = inner_message.as_ref().downcast_ref::<T::Message>() {
+            self.receive(message.clone())
+        }
+    }
+
+    /// Runs `Component::on_message`, rendering if it asks to
+    fn receive(&self, message: T::Message) {
+        let (rerender, command) = hold_events(|| {
+            T::on_message(
+                &message,
+                &*self.0.props.borrow(),
+                &mut *self.0.state.borrow_mut(),
+            )
+            .into_update()
+        });
+        if rerender {
+            self.mark_dirty()
+        }
+        self.run(command)
+    }
+
+    /// Runs the effects of a command in order.
+    /// Everything but `Command::perform` waits until the component has rendered
+    fn run(&self, command: Command<T::Message>) {
+        for effect in command.0 {
+            match effect {
+                Effect::Perform(perform) => self.receive(perform()),
+                Effect::Spawn(future) => {
+                    let instance = Rc::downgrade(&self.0);
+                    let id = spawn::<B>(async move {
+                        let message = future.await;
+                        if let Some(instance) = instance.upgrade() {
+                            ComponentWrapper(instance).receive(message)
                         }
-                    }
+                    });
+                    let mut tasks = self.0.tasks.borrow_mut();
+                    tasks.retain(|id| is_running(*id));
+                    tasks.push(id);
                 }
+                Effect::Announce(text) => after_flush::<B>(Box::new(move || B::announce(&text))),
+                Effect::Focus(key) => {
+                    self.focus_after_flush(move |sub_views| sub_views.path_to(&Key::Explicit(key)))
+                }
+                Effect::FocusPath(path) => self.focus_after_flush(move |_| {
+                    Some(path.into_iter().map(Key::Explicit).collect())
+                }),
+                Effect::Quit => after_flush::<B>(Box::new(B::quit)),
+            }
+        }
+    }
+
+    /// Focuses the widget at the end of the path `find` gives once everything has rendered
+    fn focus_after_flush(&self, find: impl FnOnce(&SubViews<B>) -> Option<Vec<Key>> + 'static) {
+        let instance = Rc::downgrade(&self.0);
+        after_flush::<B>(Box::new(move || {
+            let Some(instance) = instance.upgrade() else {
+                return;
+            };
+            let sub_views = instance.sub_views.borrow();
+            if let Some(node) = find(&sub_views).and_then(|path| sub_views.find_path(&path)) {
+                B::focus(node)
+            }
+        }))
+    }
+
+    /// Gives keyboard focus back to the node that had it before patches were applied, moving or replacing its widget can take it away.
+    /// When the node is gone focus moves to the node that took its place among its siblings, the one before it if it was the last,
+    /// or the container it was in if it was the only one, so VoiceOver users aren't sent back to the top of the window
+    fn restore_focus(&self, parent_view: &B::Container, path: &[Key], positions: &[usize]) {
+        let sub_views = self.0.sub_views.borrow();
+        if let Some(node) = sub_views.find_path(path) {
+            if !B::has_focus(node) {
+                B::focus(node)
+            }
+            return;
+        }
+        let vdom = self.0.vdom.borrow();
+        // Containers it was in may have gone as well
+        for depth in (0..path.len()).rev() {
+            let container = &path[..depth];
+            let Some(siblings) = siblings_at(&vdom, container) else {
+                continue;
+            };
+            let target = match siblings.get(positions[depth]).or(siblings.last()) {
+                Some((key, _)) => [container, std::slice::from_ref(key)].concat(),
+                None if depth == 0 => return B::focus_container(parent_view),
+                None => container.to_vec(),
+            };
+            if let Some(node) = sub_views.find_path(&target) {
+                B::focus(node)
+            }
+            return;
+        }
+    }
+
+    /// Runs the handler a click, change, select or key press was routed to
+    fn handle(&self, message: &Message) {
+        if let Payload::Key(press) = &message.payload {
+            return self.handle_key(message.id, press);
+        }
+        // Handlers are cloned out so they aren't borrowed when the component renders
+        let (rerender, command) = hold_events(|| match &message.payload {
+            Payload::Click => {
+                let handler = self.0.click_handlers.borrow().get(&message.id).cloned();
+                handler
+                    .map(|handler| {
+                        handler(&*self.0.props.borrow(), &mut *self.0.state.borrow_mut())
+                    })
+                    .unwrap_or_default()
             }
             Payload::Change(value) => {
-                let rerender =
-                    if let Some(handler) = self.change_handlers.borrow_mut().get_mut(&message.id) {
+                let handler = self.0.change_handlers.borrow().get(&message.id).cloned();
+                handler
+                    .map(|handler| {
                         handler(
                             value.as_str(),
-                            &*self.props.borrow(),
-                            &mut *self.state.borrow_mut(),
+                            &*self.0.props.borrow(),
+                            &mut *self.0.state.borrow_mut(),
                         )
-                    } else {
-                        false
-                    };
-                // We need this check in a separate block to ensure the borrow of handler is dropped
-                if rerender {
-                    self.render()
-                } else {
-                    for (_, comp) in self.vdom.borrow().iter() {
-                        if let VNode::Custom(comp) = comp {
-                            comp.renderable.0.on_message(message)
-                        }
-                    }
-                }
+                    })
+                    .unwrap_or_default()
             }
-            Payload::Select(value) => {
-                let rerender =
-                    if let Some(handler) = self.select_handlers.borrow_mut().get_mut(&message.id) {
-                        handler(*value, &*self.props.borrow(), &mut *self.state.borrow_mut())
-                    } else {
-                        false
-                    };
-                // We need this check in a separate block to ensure the borrow of handler is dropped
-                if rerender {
-                    self.render()
-                } else {
-                    for (_, comp) in self.vdom.borrow().iter() {
-                        if let VNode::Custom(comp) = comp {
-                            comp.renderable.0.on_message(message)
-                        }
+            Payload::Select(index) => {
+                let handler = self.0.select_handlers.borrow().get(&message.id).cloned();
+                handler
+                    .map(|handler| {
+                        handler(
+                            *index,
+                            &*self.0.props.borrow(),
+                            &mut *self.0.state.borrow_mut(),
+                        )
+                    })
+                    .unwrap_or_default()
+            }
+            Payload::Key(_) | Payload::Close | Payload::Custom(_) => Default::default(),
+        });
+        if rerender {
+            self.mark_dirty()
+        }
+        self.run(command)
+    }
+
+    /// Runs the `VNode::on_key_down` handler of the focused node, passing the key press on to the components shortcuts if it returns `None`
+    fn handle_key(&self, id: usize, press: &KeyPress) {
+        let handler = self.0.key_handlers.borrow().get(&id).cloned();
+        let update = handler.and_then(|handler| {
+            hold_events(|| {
+                handler(
+                    press,
+                    &*self.0.props.borrow(),
+                    &mut *self.0.state.borrow_mut(),
+                )
+            })
+        });
+        let Some((rerender, command)) = update else {
+            return self.key_down(press);
+        };
+        key_handled();
+        if rerender {
+            self.mark_dirty()
+        }
+        self.run(command)
+    }
+
+    /// Sends the message of the first shortcut the key press matches, or passes it on to the component this one is nested in
+    fn key_down(&self, press: &KeyPress) {
+        let message = T::shortcuts(&*self.0.props.borrow(), &*self.0.state.borrow())
+            .into_iter()
+            .find(|(chord, _)| chord.matches(press))
+            .map(|(_, message)| message);
+        if let Some(message) = message {
+            key_handled();
+            return self.receive(message);
+        }
+        let parent = self.0.key_parent.borrow().as_ref().and_then(Weak::upgrade);
+        if let Some(parent) = parent {
+            parent(press)
+        }
+    }
+
+    /// Passes a custom message on to every nested component, including those inside containers
+    fn forward_to_children(&self, message: &Message) {
+        fn forward<T: Component>(node: &VNode<T>, message: &Message) {
+            match node {
+                VNode::Custom(component) => component.renderable().on_message(message),
+                VNode::Container(container) => {
+                    for (_, child) in &container.children {
+                        forward(child, message)
                     }
                 }
+                VNode::With(_, node) => forward(node, message),
+                _ => {}
             }
-            Payload::Custom(inner_message) => {
-                for (_, comp) in self.vdom.borrow().iter() {
-                    if let VNode::Custom(comp) = comp {
-                        comp.renderable.0.on_message(message)
+        }
+        for (_, node) in self.0.vdom.borrow().iter() {
+            forward(node, message)
+        }
+    }
+
+    /// How many handlers the component has registered.
+    /// Handlers are unregistered along with the node they belong to so this only counts those of mounted nodes, which tests can check to catch leaks
+    pub fn handler_count(&self) -> usize {
+        self.0.click_handlers.borrow().len()
+            + self.0.change_handlers.borrow().len()
+            + self.0.select_handlers.borrow().len()
+            + self.0.key_handlers.borrow().len()
+    }
+
+    /// Renders the component with new props on the next `flush`, unless they are the same as the ones it has
+    pub fn update_props(&self, props: T::Props) {
+        if *self.0.props.borrow() == props {
+            return;
+        }
+        *self.0.props.borrow_mut() = props;
+        self.mark_dirty();
+    }
+
+    /// Starts subscriptions that weren't returned after the last render and stops those that no longer are
+    fn update_subscriptions(&self, props: &T::Props, state: &T::State) {
+        let mut running = self.0.subscriptions.take();
+        let subscriptions = T::subscriptions(props, state)
+            .into_iter()
+            .map(|subscription| {
+                match running.iter().position(|(other, _)| *other == subscription) {
+                    Some(index) => running.swap_remove(index),
+                    None => {
+                        let handle = self.subscribe(&subscription);
+                        (subscription, handle)
                     }
                 }
-                let rerender =
-                    if let Some(message) = inner_message.as_ref().downcast_ref::<T::Message>() {
-                        T::on_message(
-                            message,
-                            &*self.props.borrow(),
-                            &mut *self.state.borrow_mut(),
-                        )
-                    } else {
-                        false
-                    };
-                if rerender {
-                    self.render()
-                }
+            })
+            .collect();
+        // Dropping the rest stops them
+        *self.0.subscriptions.borrow_mut() = subscriptions;
+    }
+
+    fn subscribe(&self, subscription: &Subscription<T::Message>) -> Box<dyn Any> {
+        let instance = Rc::downgrade(&self.0);
+        subscription.start::<B>(Rc::new(move |message| {
+            if let Some(instance) = instance.upgrade() {
+                ComponentWrapper(instance).receive(message)
             }
+        }))
+    }
+
+    /// Opens windows that weren't returned after the last render, brings the rest up to date and closes those that no longer are
+    fn update_windows(&self) {
+        let windows = rendering::<T, _>(&self.0.receiver, || {
+            T::windows(&*self.0.props.borrow(), &*self.0.state.borrow())
+        });
+        let mut open = self.0.windows.take();
+        let windows = windows
+            .into_iter()
+            .map(
+                |window| match open.iter().position(|other| other.window.key == window.key) {
+                    Some(index) => self.update_window(open.swap_remove(index), window),
+                    None => self.open_window(window),
+                },
+            )
+            .collect();
+        for window in open {
+            Self::close_window(window)
         }
+        *self.0.windows.borrow_mut() = windows;
     }
 
-    fn has_handler_for(&self, id: &usize) -> bool {
-        self.click_handlers.borrow().contains_key(id)
-            || self.change_handlers.borrow().contains_key(id)
+    fn open_window(&self, window: VWindow<T>) -> OpenWindow<T, B> {
+        let close = gen_id();
+        let instance = Rc::downgrade(&self.0);
+        let key = window.key.clone();
+        add_route(
+            close,
+            Rc::new(move |_| {
+                if let Some(instance) = instance.upgrade() {
+                    ComponentWrapper(instance).window_closed(&key)
+                }
+            }),
+        );
+        let content = window.content.build::<B>();
+        content.set_depth(self.0.depth.get() + 1);
+        content.set_key_parent(Rc::downgrade(&self.0.keys));
+        let native = B::open_window(&window.attributes, &window.content, close);
+        OpenWindow {
+            window,
+            native,
+            _close: Registration(Some(Box::new(move || remove_route(close)))),
+        }
     }
-    pub fn update_props(&self, props: T::Props) {
-        *self.props.borrow_mut() = props;
-        self.render();
+
+    /// Updates the props of the windows content and any attributes that changed, reopening it if the content is a different component
+    fn update_window(&self, mut open: OpenWindow<T, B>, window: VWindow<T>) -> OpenWindow<T, B> {
+        if open.window.content.type_id != window.content.type_id {
+            Self::close_window(open);
+            return self.open_window(window);
+        }
+        if open.window.content != window.content {
+            open.window.content.update_from(window.content);
+        }
+        if open.window.attributes != window.attributes {
+            B::update_window(
+                &mut open.native,
+                &open.window.attributes,
+                &window.attributes,
+            );
+        }
+        open.window.attributes = window.attributes;
+        open.window.on_close = window.on_close;
+        open
+    }
+
+    fn close_window(open: OpenWindow<T, B>) {
+        open.window.content.renderable().will_unmount();
+        B::close_window(open.native);
+    }
+
+    /// Forgets a window the user closed and runs its `on_close` handler.
+    /// The windows are checked again afterwards, so one that is still returned opens again
+    fn window_closed(&self, key: &KeyValue) {
+        let closed = {
+            let mut windows = self.0.windows.borrow_mut();
+            let index = windows.iter().position(|open| open.window.key == *key);
+            index.map(|index| windows.remove(index))
+        };
+        let Some(closed) = closed else {
+            return;
+        };
+        closed.window.content.renderable().will_unmount();
+        let (_, command) = hold_events(|| {
+            closed
+                .window
+                .on_close
+                .as_ref()
+                .map(|handler| handler(&*self.0.props.borrow(), &mut *self.0.state.borrow_mut()))
+                .unwrap_or_default()
+        });
+        self.mark_dirty();
+        self.run(command)
+    }
+
+    /// Hands the menus returned from `Component::menu_bar` to the menu bar
+    fn update_menu_bar(&self) {
+        let menus = rendering::<T, _>(&self.0.receiver, || {
+            T::menu_bar(&*self.0.props.borrow(), &*self.0.state.borrow())
+        });
+        let mut old = self.0.menu_bar.take().into_iter();
+        let menus = menus
+            .into_iter()
+            .map(|menu| {
+                let ids = self.bind_menu(&menu, old.next());
+                (menu, ids)
+            })
+            .collect::<Vec<_>>();
+        set_menu_bar::<B>(
+            self.owner_id(),
+            menus
+                .iter()
+                .map(|(menu, ids)| native_menu(menu, ids))
+                .collect(),
+        );
+        *self.0.menu_bar.borrow_mut() = menus;
     }
 
-    pub fn create_component(&self, vnode: &mut VNode<T>) -> CacaoComponent<T, D> {
+    /// Tells the components menus in the menu bar apart from those of other components
+    fn owner_id(&self) -> usize {
+        Rc::as_ptr(&self.0) as *const () as usize
+    }
+
+    /// Registers the handlers of a menus items.
+    /// Items whose handler is the same as that of the item in the same position of `old` keep its id
+    fn bind_menu(&self, menu: &VMenu<T>, old: Option<(VMenu<T>, MenuIds)>) -> MenuIds {
+        let mut old = old
+            .map(|(menu, ids)| menu.items.into_iter().zip(ids).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter();
+        menu.items
+            .iter()
+            .map(|item| match old.next() {
+                Some((old_item, ids)) if old_item.on_select == item.on_select => ids,
+                _ => item
+                    .on_select
+                    .as_ref()
+                    .map(|handler| self.register(&self.0.click_handlers, handler)),
+            })
+            .collect()
+    }
+
+    /// Renders the component on the next `flush`.
+    /// Marking it more than once before then still only renders it once
+    pub fn mark_dirty(&self) {
+        if !self.0.dirty.replace(true) {
+            let instance: Weak<dyn Scheduled> = Rc::downgrade(&self.0) as _;
+            schedule::<B>(instance)
+        }
+    }
+
+    /// Creates the widget for a node and those of its children.
+    /// The nodes handler and the widgets of its children are kept track of under `key` in `siblings`
+    fn create_node(&self, key: &Key, vnode: &mut VNode<T>, siblings: &mut SubViews<B>) -> B::Node {
         match vnode {
             VNode::Custom(component) => {
-                let view = View::with(component.renderable.clone());
-                CacaoComponent::View(view)
-            }
-            VNode::Label(data) => {
-                let label = Label::new();
-                label.set_text(&data.text);
-                CacaoComponent::Label(label)
-            }
-            VNode::Text(text) => {
-                let label = Label::new();
-                label.set_text(text);
-                CacaoComponent::Label(label)
+                let renderable = component.build::<B>();
+                renderable.set_depth(self.0.depth.get() + 1);
+                renderable.set_key_parent(Rc::downgrade(&self.0.keys));
+                B::create_custom(component)
             }
+            VNode::Label(data) => B::create_label(&data.text),
+            VNode::Text(text) => B::create_label(text),
             VNode::Button(button) => {
-                let mut btn = Button::new(button.text.as_ref());
-                if let Some(handler) = button.click {
-                    let id = gen_id();
-                    self.click_handlers.borrow_mut().insert(id, handler);
-                    btn.set_action(move |_| App::<D, Message>::dispatch_main(Message::click(id)));
-                }
-                CacaoComponent::Button(btn)
+                let mut btn = B::create_button(&button.text);
+                self.bind(
+                    &self.0.click_handlers,
+                    &button.click,
+                    &mut btn,
+                    key,
+                    &mut siblings.registrations,
+                );
+                btn
             }
             VNode::Select(select) => {
-                let mut select_view = Select::new();
-                for option in &select.options {
-                    select_view.add_item(option)
-                }
-                if let Some(handler) = select.select {
-                    let id = gen_id();
-                    self.select_handlers.borrow_mut().insert(id, handler);
-                    select_view.set_action(move |sender| {
-                        let index: NSInteger = unsafe { msg_send![sender, indexOfSelectedItem] };
-                        App::<D, Message>::dispatch_main(Message::select(id, index as usize))
-                    });
+                let mut select_view = B::create_select(&select.options);
+                if select.selected.is_some() {
+                    B::set_selected(&mut select_view, select.selected);
                 }
-                CacaoComponent::Select(select_view)
+                self.bind(
+                    &self.0.select_handlers,
+                    &select.select,
+                    &mut select_view,
+                    key,
+                    &mut siblings.registrations,
+                );
+                select_view
             }
             VNode::TextInput(text_input) => {
-                let id = gen_id();
-                let input = TextField::with(TextInput::new(id));
-                input.set_text(&text_input.initial_value);
-                if let Some(handler) = text_input.change {
-                    self.change_handlers.borrow_mut().insert(id, handler);
-                };
-                CacaoComponent::TextField(input)
+                let mut input = B::create_text_input(&text_input.initial_value);
+                self.bind(
+                    &self.0.change_handlers,
+                    &text_input.change,
+                    &mut input,
+                    key,
+                    &mut siblings.registrations,
+                );
+                input
             }
             VNode::List(list) => {
-                eprintln!("processing VList of {}", type_name::<T>());
-                let list = MyListView::<T, D>::with(
-                    list.count,
-                    list.render,
-                    self.props.clone(),
-                    self.state.clone(),
+                let rows = Rc::new(RefCell::new(SubViews::default()));
+                siblings.lists.insert(key.clone(), Rc::clone(&rows));
+                B::create_list(list, self.list_rows(rows))
+            }
+            VNode::Container(container) => {
+                let group = B::create_group(&container.kind);
+                let parent = B::as_container(&group).expect("Groups must be containers");
+                let mut children = SubViews::default();
+                for (key, child) in &mut container.children {
+                    self.mount_node(parent, &mut children, key.clone(), child, None);
+                }
+                B::apply_layout(
+                    parent,
+                    &container
+                        .children
+                        .iter()
+                        .map(|(key, _)| &children.nodes[key])
+                        .collect::<Vec<_>>(),
+                );
+                siblings.nested.insert(key.clone(), children);
+                group
+            }
+            VNode::With(attributes, node) => {
+                let mut view = self.create_node(key, node, siblings);
+                if let Some(menu) = &attributes.context_menu {
+                    let ids = self.bind_menu(menu, None);
+                    B::set_context_menu(&mut view, Some(&native_menu(menu, &ids)));
+                    siblings.menus.insert(key.clone(), ids);
+                }
+                self.bind_key_down(
+                    &attributes.on_key_down,
+                    &mut view,
+                    key,
+                    &mut siblings.key_registrations,
                 );
-                CacaoComponent::List(list)
+                if attributes.autofocus {
+                    siblings.autofocus.get_or_insert_with(|| key.clone());
+                }
+                view
             }
+            VNode::Keyed(_, node) => self.create_node(key, node, siblings),
         }
     }
 
-    fn diff_nodes(&self, a: &VNode<T>, b: VNode<T>) -> Vec<VDomDiff<T>> {
-        match (a, b) {
-            (VNode::Label(a), VNode::Label(b)) => {
-                if a.text != b.text {
-                    vec![VDomDiff::UpdateLabelText(b.text)]
-                } else {
-                    Vec::new()
+    /// A handle for a list to create its rows with, keeping track of them in `rows`
+    fn list_rows(&self, rows: Rc<RefCell<SubViews<B>>>) -> ListRows<T, B> {
+        ListRows {
+            instance: Rc::downgrade(&self.0),
+            rows,
+        }
+    }
+
+    /// Points a node at a newly registered handler, or disconnects it, dropping the registration of any handler it had before
+    fn bind<H: Clone + 'static>(
+        &self,
+        handlers: &Handlers<H>,
+        handler: &Option<H>,
+        node: &mut B::Node,
+        key: &Key,
+        registrations: &mut HashMap<Key, Registration>,
+    ) {
+        if let Some(handler) = handler {
+            let (id, registration) = self.register(handlers, handler);
+            registrations.insert(key.clone(), registration);
+            B::set_handler(node, Some(id));
+        } else if registrations.remove(key).is_some() {
+            B::set_handler(node, None);
+        }
+    }
+
+    /// Points the key presses of a node at its `VNode::on_key_down` handler like `bind` does for other handlers
+    fn bind_key_down(
+        &self,
+        handler: &Option<KeyDownHandler<T>>,
+        node: &mut B::Node,
+        key: &Key,
+        registrations: &mut HashMap<Key, Registration>,
+    ) {
+        if let Some(handler) = handler {
+            let (id, registration) = self.register(&self.0.key_handlers, handler);
+            registrations.insert(key.clone(), registration);
+            B::set_key_handler(node, Some(id));
+        } else if registrations.remove(key).is_some() {
+            B::set_key_handler(node, None);
+        }
+    }
+
+    /// Registers a handler under a new id and routes messages with that id to it
+    fn register<H: Clone + 'static>(
+        &self,
+        handlers: &Handlers<H>,
+        handler: &H,
+    ) -> (usize, Registration) {
+        let id = gen_id();
+        handlers.borrow_mut().insert(id, handler.clone());
+        let instance = Rc::downgrade(&self.0);
+        add_route(
+            id,
+            Rc::new(move |message| {
+                if let Some(instance) = instance.upgrade() {
+                    ComponentWrapper(instance).handle(message)
                 }
-            }
-            (VNode::Text(a), VNode::Text(b)) => {
-                if *a != b {
-                    vec![VDomDiff::UpdatePureText(b)]
-                } else {
-                    Vec::new()
+            }),
+        );
+        (id, Registration::new(handlers, id))
+    }
+
+    /// Creates the widget for a node and adds it to `parent` in front of the sibling `before`
+    fn mount_node(
+        &self,
+        parent: &B::Container,
+        sub_views: &mut SubViews<B>,
+        key: Key,
+        node: &mut VNode<T>,
+        before: Option<&Key>,
+    ) {
+        let view = self.create_node(&key, node, sub_views);
+        B::insert_child(parent, &view, before.map(|before| &sub_views.nodes[before]));
+        sub_views.nodes.insert(key, view);
+    }
+
+    fn unmount_node(parent: &B::Container, sub_views: &mut SubViews<B>, key: &Key) {
+        if let Some(view) = sub_views.nodes.remove(key) {
+            B::remove_child(parent, &view)
+        }
+        sub_views.nested.remove(key);
+        sub_views.registrations.remove(key);
+        sub_views.menus.remove(key);
+        sub_views.key_registrations.remove(key);
+        sub_views.lists.remove(key);
+    }
+
+    /// Applies patches from `reconcile` to a list of siblings rendered into `parent`, recursing into containers whose children changed
+    fn apply_patches(
+        &self,
+        parent_view: &B::Container,
+        vdom: &mut VDom<T>,
+        sub_views: &mut SubViews<B>,
+        patches: Vec<Patch<T>>,
+    ) {
+        // Where the node in front of `before` belongs in the vdom while it is being rearranged
+        let position_before = |vdom: &VDom<T>, before: &Option<Key>| match before {
+            Some(before) => vdom.iter().position(|(key, _)| key == before).unwrap(),
+            None => vdom.len(),
+        };
+        for Patch {
+            key,
+            index,
+            before,
+            diff,
+        } in patches
+        {
+            let views = &mut sub_views.nodes;
+            match diff {
+                VDomDiff::Remove => {
+                    will_unmount(&vdom.remove(index).1);
+                    Self::unmount_node(parent_view, sub_views, &key);
                 }
-            }
-            (VNode::Button(a), VNode::Button(b)) => {
-                let mut changes = Vec::new();
-                if a.text != b.text {
-                    changes.push(VDomDiff::UpdateButtonText(b.text))
+                VDomDiff::Move => {
+                    let from = vdom.iter().position(|(other, _)| *other == key).unwrap();
+                    let node = vdom.remove(from);
+                    let position = position_before(vdom, &before);
+                    vdom.insert(position, node);
+                    B::move_child(
+                        parent_view,
+                        &views[&key],
+                        before.as_ref().map(|before| &views[before]),
+                    );
                 }
-                if a.click != b.click {
-                    changes.push(VDomDiff::UpdateButtonClick(b.click))
+                VDomDiff::InsertNode(mut node) => {
+                    self.mount_node(
+                        parent_view,
+                        sub_views,
+                        key.clone(),
+                        &mut node,
+                        before.as_ref(),
+                    );
+                    let position = position_before(vdom, &before);
+                    vdom.insert(position, (key, node));
                 }
-                changes
-            }
-            (VNode::Custom(a), VNode::Custom(b)) => {
-                if *a == b {
-                    Vec::new()
-                } else if a.renderable.0.same_component_as(b.renderable.0.as_ref()) {
-                    vec![VDomDiff::UpdatePropsFrom(b)]
-                } else {
-                    // Both are custom components but different kinds so we must replace it
-                    vec![VDomDiff::ReplaceWith(VNode::Custom(b))]
+                VDomDiff::ReplaceWith(mut node) => {
+                    will_unmount(&vdom[index].1);
+                    Self::unmount_node(parent_view, sub_views, &key);
+                    self.mount_node(parent_view, sub_views, key, &mut node, before.as_ref());
+                    vdom[index].1 = node;
+                }
+                VDomDiff::UpdateChildren(patches) => {
+                    let container =
+                        B::as_container(&views[&key]).expect("Groups must be containers");
+                    self.apply_patches(
+                        container,
+                        &mut vdom[index]
+                            .1
+                            .content_mut()
+                            .as_container_mut()
+                            .unwrap()
+                            .children,
+                        sub_views.nested.get_mut(&key).unwrap(),
+                        patches,
+                    );
+                }
+                VDomDiff::UpdateTitle(title) => {
+                    B::set_text(views.get_mut(&key).unwrap(), &title);
+                    vdom[index].1.content_mut().as_container_mut().unwrap().kind =
+                        ContainerKind::Box(title);
+                }
+                VDomDiff::UpdateLabelText(text) => {
+                    B::set_text(views.get_mut(&key).unwrap(), &text);
+                    vdom[index].1.content_mut().as_label_mut().unwrap().text = text;
+                }
+                VDomDiff::UpdatePureText(text) => {
+                    B::set_text(views.get_mut(&key).unwrap(), text);
+                    *vdom[index].1.content_mut().as_text_mut().unwrap() = text;
+                }
+                VDomDiff::UpdateButtonText(text) => {
+                    B::set_text(views.get_mut(&key).unwrap(), &text);
+                    vdom[index].1.content_mut().as_button_mut().unwrap().text = text;
+                }
+                VDomDiff::UpdateButtonClick(handler) => {
+                    self.bind(
+                        &self.0.click_handlers,
+                        &handler,
+                        views.get_mut(&key).unwrap(),
+                        &key,
+                        &mut sub_views.registrations,
+                    );
+                    vdom[index].1.content_mut().as_button_mut().unwrap().click = handler;
+                }
+                VDomDiff::UpdateInputChange(handler) => {
+                    self.bind(
+                        &self.0.change_handlers,
+                        &handler,
+                        views.get_mut(&key).unwrap(),
+                        &key,
+                        &mut sub_views.registrations,
+                    );
+                    vdom[index]
+                        .1
+                        .content_mut()
+                        .as_text_input_mut()
+                        .unwrap()
+                        .change = handler;
+                }
+                VDomDiff::UpdateInputValue(value) => {
+                    B::set_text(views.get_mut(&key).unwrap(), &value);
+                    vdom[index]
+                        .1
+                        .content_mut()
+                        .as_text_input_mut()
+                        .unwrap()
+                        .initial_value = value;
+                }
+                VDomDiff::UpdateSelectOptions(options) => {
+                    let select_view = views.get_mut(&key).unwrap();
+                    let select = vdom[index].1.content_mut().as_select_mut().unwrap();
+                    B::set_options(select_view, &options);
+                    // Replacing the options loses the native selection
+                    B::set_selected(select_view, select.selected);
+                    select.options = options;
+                }
+                VDomDiff::UpdateSelection(selected) => {
+                    B::set_selected(views.get_mut(&key).unwrap(), selected);
+                    vdom[index]
+                        .1
+                        .content_mut()
+                        .as_select_mut()
+                        .unwrap()
+                        .selected = selected;
+                }
+                VDomDiff::UpdateSelectHandler(handler) => {
+                    self.bind(
+                        &self.0.select_handlers,
+                        &handler,
+                        views.get_mut(&key).unwrap(),
+                        &key,
+                        &mut sub_views.registrations,
+                    );
+                    vdom[index].1.content_mut().as_select_mut().unwrap().select = handler;
+                }
+                VDomDiff::UpdateList(list) => {
+                    let current = vdom[index].1.content_mut().as_list_mut().unwrap();
+                    *current = list;
+                    let rows = self.list_rows(Rc::clone(&sub_views.lists[&key]));
+                    rows.clear();
+                    B::update_list(views.get_mut(&key).unwrap(), current, &rows);
+                }
+                VDomDiff::UpdateContextMenu(menu) => {
+                    let current = &mut vdom[index].1.attributes_mut().context_menu;
+                    let old = std::mem::replace(current, menu);
+                    let old_ids = sub_views.menus.remove(&key).unwrap_or_default();
+                    let node = views.get_mut(&key).unwrap();
+                    match current {
+                        Some(menu) => {
+                            let ids = self.bind_menu(menu, old.map(|old| (old, old_ids)));
+                            B::set_context_menu(node, Some(&native_menu(menu, &ids)));
+                            sub_views.menus.insert(key, ids);
+                        }
+                        None => B::set_context_menu(node, None),
+                    }
+                }
+                VDomDiff::UpdateKeyDown(handler) => {
+                    self.bind_key_down(
+                        &handler,
+                        views.get_mut(&key).unwrap(),
+                        &key,
+                        &mut sub_views.key_registrations,
+                    );
+                    vdom[index].1.attributes_mut().on_key_down = handler;
+                }
+                VDomDiff::UpdatePropsFrom(component) => {
+                    vdom[index]
+                        .1
+                        .content_mut()
+                        .as_custom_mut()
+                        .unwrap()
+                        .update_from(component);
                 }
             }
-            (_, b) => vec![VDomDiff::ReplaceWith(b)],
         }
+        let views_to_render = vdom
+            .iter()
+            .map(|(key, _)| sub_views.nodes.get(key).unwrap())
+            .collect::<Vec<_>>();
+        B::apply_layout(parent_view, &views_to_render);
     }
 }
 
@@ -292,9 +1137,128 @@ pub enum VNode<T: Component + ?Sized> {
     Select(VSelect<T>),
     Text(&'static str),
     Custom(VComponent),
+    Container(VContainer<T>),
+    /// A node given an explicit key, see `VNode::key`
+    Keyed(KeyValue, Box<VNode<T>>),
+    /// A node given attributes any kind of node can have, see `VNode::context_menu` and `VNode::on_key_down`
+    With(VAttributes<T>, Box<VNode<T>>),
+}
+
+/// What can be added to any node, kept apart from the node so it is diffed the same way whatever the node is
+pub struct VAttributes<T: Component + ?Sized> {
+    pub context_menu: Option<VMenu<T>>,
+    pub on_key_down: Option<KeyDownHandler<T>>,
+    /// Only looked at when the nodes widget is created
+    pub autofocus: bool,
+}
+
+// Derived implementations would need the component to implement them as well
+impl<T: Component + ?Sized> Default for VAttributes<T> {
+    fn default() -> Self {
+        Self {
+            context_menu: None,
+            on_key_down: None,
+            autofocus: false,
+        }
+    }
+}
+
+impl<T: Component + ?Sized> PartialEq for VAttributes<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.context_menu == other.context_menu
+            && self.on_key_down == other.on_key_down
+            && self.autofocus == other.autofocus
+    }
 }
 
 impl<T: Component + ?Sized> VNode<T> {
+    /// Keys the node so it keeps its widget when its siblings are added, removed or reordered around it.
+    /// Without a key a node is matched up with whatever was rendered in the same position last time.
+    /// Any hashable value can be a key but it must be the same type every render, `1_u32` won't match `1_usize`
+    pub fn key(self, key: impl Hash + Eq + Debug + 'static) -> Self {
+        let key = KeyValue::new(key);
+        match self {
+            Self::Keyed(_, node) => Self::Keyed(key, node),
+            node => Self::Keyed(key, Box::new(node)),
+        }
+    }
+
+    /// Gives the node a menu that opens when it is right clicked or VoiceOver is asked for its actions
+    pub fn context_menu(self, menu: VMenu<T>) -> Self {
+        self.attribute(|attributes| attributes.context_menu = Some(menu))
+    }
+
+    /// Runs the handler for keys pressed while the node has keyboard focus.
+    /// A handler returning `None` passes the key press on to `Component::shortcuts`, and from there to the components the node is nested in
+    pub fn on_key_down(self, handler: KeyDownHandler<T>) -> Self {
+        self.attribute(|attributes| attributes.on_key_down = Some(handler))
+    }
+
+    /// Gives the node keyboard focus once its widget is created, when it is first rendered or when it replaces another node.
+    /// Only one node should be given it per render, a node in a container loses out to ones outside of it
+    pub fn autofocus(self) -> Self {
+        self.attribute(|attributes| attributes.autofocus = true)
+    }
+
+    fn attribute(self, set: impl FnOnce(&mut VAttributes<T>)) -> Self {
+        match self {
+            // Kept outermost so the key is still found
+            Self::Keyed(key, node) => Self::Keyed(key, Box::new(node.attribute(set))),
+            Self::With(mut attributes, node) => {
+                set(&mut attributes);
+                Self::With(attributes, node)
+            }
+            node => {
+                let mut attributes = VAttributes::default();
+                set(&mut attributes);
+                Self::With(attributes, Box::new(node))
+            }
+        }
+    }
+
+    /// The node itself, looking past any attributes it was given
+    pub fn content(&self) -> &VNode<T> {
+        match self {
+            Self::With(_, node) => node.content(),
+            node => node,
+        }
+    }
+
+    /// The node itself, looking past any attributes it was given
+    pub fn content_mut(&mut self) -> &mut VNode<T> {
+        match self {
+            Self::With(_, node) => node.content_mut(),
+            node => node,
+        }
+    }
+
+    /// The attributes of the node, wrapping it in default ones if it has none yet
+    pub(crate) fn attributes_mut(&mut self) -> &mut VAttributes<T> {
+        if !matches!(self, Self::With(..)) {
+            let node = std::mem::replace(self, Self::Text(""));
+            *self = Self::With(VAttributes::default(), Box::new(node));
+        }
+        let Self::With(attributes, _) = self else {
+            unreachable!()
+        };
+        attributes
+    }
+
+    /// Stacks the children top to bottom
+    pub fn stack(children: Vec<VNode<T>>) -> Self {
+        Self::Container(VContainer::new(ContainerKind::Stack, children))
+    }
+
+    /// Stacks the children in a group that screen readers announce and move into as one element
+    pub fn group(children: Vec<VNode<T>>) -> Self {
+        Self::Container(VContainer::new(ContainerKind::Group, children))
+    }
+
+    /// A group announced with a title
+    pub fn titled_box(title: impl Into<String>, children: Vec<VNode<T>>) -> Self {
+        Self::Container(VContainer::new(ContainerKind::Box(title.into()), children))
+    }
+
     pub fn as_button(&self) -> Option<&VButton<T>> {
         if let Self::Button(v) = self {
             Some(v)
@@ -335,6 +1299,14 @@ impl<T: Component + ?Sized> VNode<T> {
         }
     }
 
+    pub fn as_custom_mut(&mut self) -> Option<&mut VComponent> {
+        if let Self::Custom(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
     pub fn as_text_input(&self) -> Option<&VTextInput<T>> {
         if let Self::TextInput(v) = self {
             Some(v)
@@ -351,32 +1323,64 @@ impl<T: Component + ?Sized> VNode<T> {
         }
     }
 
-    pub fn as_text(&self) -> Option<&&'static str> {
-        if let Self::Text(v) = self {
+    pub fn as_text(&self) -> Option<&&'static str> {
+        if let Self::Text(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_text_mut(&mut self) -> Option<&mut &'static str> {
+        if let Self::Text(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_select(&self) -> Option<&VSelect<T>> {
+        if let Self::Select(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_select_mut(&mut self) -> Option<&mut VSelect<T>> {
+        if let Self::Select(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&VList<T>> {
+        if let Self::List(v) = self {
             Some(v)
         } else {
             None
         }
     }
 
-    pub fn as_text_mut(&mut self) -> Option<&mut &'static str> {
-        if let Self::Text(v) = self {
+    pub fn as_list_mut(&mut self) -> Option<&mut VList<T>> {
+        if let Self::List(v) = self {
             Some(v)
         } else {
             None
         }
     }
 
-    pub fn as_select(&self) -> Option<&VSelect<T>> {
-        if let Self::Select(v) = self {
+    pub fn as_container(&self) -> Option<&VContainer<T>> {
+        if let Self::Container(v) = self {
             Some(v)
         } else {
             None
         }
     }
 
-    pub fn as_select_mut(&mut self) -> Option<&mut VSelect<T>> {
-        if let Self::Select(v) = self {
+    pub fn as_container_mut(&mut self) -> Option<&mut VContainer<T>> {
+        if let Self::Container(v) = self {
             Some(v)
         } else {
             None
@@ -401,318 +1405,371 @@ pub struct VTextInput<T: Component + ?Sized> {
     pub initial_value: String,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
 pub struct VList<T: Component + ?Sized> {
     pub count: usize,
-    pub render: fn(index: usize, &T::Props, &T::State) -> Vec<VNode<T>>,
+    /// Compared by address, which is only a best guess for functions as the same function can have a different address in another codegen unit and different functions can be merged into one.
+    /// The rows are rendered again every time the component renders, so this only matters when comparing nodes
+    pub render: ListRender<T>,
+}
+
+impl<T: Component + ?Sized> PartialEq for VList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count && std::ptr::fn_addr_eq(self.render, other.render)
+    }
 }
 
 #[derive(PartialEq, Clone)]
 pub struct VSelect<T: Component + ?Sized> {
     pub options: Vec<String>,
+    /// The index of the option to show as chosen, the first option is shown when this is `None`
+    pub selected: Option<usize>,
     pub select: Option<SelectHandler<T>>,
 }
 
+/// Lays out nodes inside a view of their own without needing a separate component.
+/// The children are rendered and diffed by the component that rendered the container, so their handlers update its state
+#[derive(PartialEq)]
+pub struct VContainer<T: Component + ?Sized> {
+    pub kind: ContainerKind,
+    pub(crate) children: Vec<(Key, VNode<T>)>,
+}
+
+impl<T: Component + ?Sized> VContainer<T> {
+    pub fn new(kind: ContainerKind, children: Vec<VNode<T>>) -> Self {
+        Self {
+            kind,
+            children: assign_keys(children),
+        }
+    }
+
+    pub fn children(&self) -> &[(Key, VNode<T>)] {
+        &self.children
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ContainerKind {
+    Stack,
+    Group,
+    /// A group with a title
+    Box(String),
+}
+
+/// A component rendered inside another.
+/// It is made with the backend of the component that renders it when its node is created, so the same tree renders with any backend
 pub struct VComponent {
     pub type_id: TypeId,
-    pub renderable: RenderableWrapper,
+    factory: Rc<dyn ComponentFactory>,
+    renderable: OnceCell<Rc<dyn Renderable>>,
 }
 
 impl VComponent {
-    pub fn new<T, D>(props: T::Props) -> Self
+    pub fn new<T>(props: T::Props) -> Self
     where
         T: Component + Clone + PartialEq + 'static,
-        D: AppDelegate + Dispatcher<Message> + 'static,
     {
         Self {
             type_id: TypeId::of::<T>(),
-            renderable: RenderableWrapper(Rc::new(ComponentWrapper::<T, D>::new(props))),
+            factory: Rc::new(Factory::<T>(props)),
+            renderable: OnceCell::new(),
         }
     }
+
+    /// Makes the component with the backend `B` the first time it is called
+    pub(crate) fn build<B: Backend>(&self) -> &Rc<dyn Renderable> {
+        self.renderable.get_or_init(|| {
+            self.factory
+                .create(TypeId::of::<B>())
+                .unwrap_or_else(|| panic!("{} is not one of the `Backends`", type_name::<B>()))
+        })
+    }
+
+    /// The component made when the node was created
+    pub fn renderable(&self) -> &Rc<dyn Renderable> {
+        self.renderable
+            .get()
+            .expect("The component is made when its node is created")
+    }
+
+    /// Gives the component made from this node the props of a newer one
+    pub(crate) fn update_from(&mut self, other: VComponent) {
+        self.renderable().update_props_from(other.factory.props());
+        self.factory = other.factory;
+    }
 }
 
 impl PartialEq for VComponent {
     fn eq(&self, other: &Self) -> bool {
-        self.type_id == other.type_id && self.renderable.0.equal_to(other.renderable.0.as_ref())
+        self.type_id == other.type_id && self.factory.props_equal(other.factory.props())
+    }
+}
+
+/// Makes a nested component with the backend of the component that renders it
+trait ComponentFactory {
+    /// Makes the component with the backend whose type id is `backend`, `None` if it isn't one of `Backends`
+    fn create(&self, backend: TypeId) -> Option<Rc<dyn Renderable>>;
+    /// The props the component is made with
+    fn props(&self) -> &dyn Any;
+    /// Whether `other` is the same props, false when they belong to another type of component
+    fn props_equal(&self, other: &dyn Any) -> bool;
+}
+
+struct Factory<T: Component>(T::Props);
+
+impl<T: Component + Clone + PartialEq + 'static> ComponentFactory for Factory<T> {
+    fn create(&self, backend: TypeId) -> Option<Rc<dyn Renderable>> {
+        Backends::mount::<T>(backend, &self.0)
+    }
+
+    fn props(&self) -> &dyn Any {
+        &self.0
+    }
+
+    fn props_equal(&self, other: &dyn Any) -> bool {
+        other
+            .downcast_ref::<T::Props>()
+            .is_some_and(|other| self.0 == *other)
     }
 }
 
-type ClickHandler<T> = fn(&<T as Component>::Props, &mut <T as Component>::State);
-type ChangeHandler<T> = fn(&str, &<T as Component>::Props, &mut <T as Component>::State) -> bool;
-type SelectHandler<T> = fn(usize, &<T as Component>::Props, &mut <T as Component>::State) -> bool;
+pub type ListRender<T> =
+    fn(usize, &<T as Component>::Props, &<T as Component>::State) -> Vec<VNode<T>>;
 
 pub trait Renderable {
     fn copy(&self) -> Rc<dyn Renderable>;
     fn as_any(&self) -> &dyn Any;
-    fn equal_to(&self, other: &dyn Renderable) -> bool;
-    fn same_component_as(&self, other: &dyn Renderable) -> bool;
-    fn update_props_from(&self, other: Rc<dyn Renderable>);
+    /// The props must be those of the component
+    fn update_props_from(&self, props: &dyn Any);
     fn render(&self);
-    fn set_parent_view(&self, view: View);
+    /// Runs `Component::will_unmount` for the component and every component nested in it
+    fn will_unmount(&self);
+    /// How many components the component is nested in, set by the component that renders it
+    fn set_depth(&self, depth: usize);
+    /// The view must be the `Backend::Container` of the backend the component was created with
+    fn set_parent_view(&self, view: Box<dyn Any>);
+    /// Where key presses the component doesn't handle go, set by the component that renders it
+    fn set_key_parent(&self, parent: KeyParent);
     fn on_message(&self, message: &Message);
+    /// The name of the component type without its module path
+    fn name(&self) -> &'static str;
+    /// Writes a line for each node in the virtual DOM, indented by `depth` levels
+    fn write_snapshot(&self, depth: usize, out: &mut String);
 }
 
-impl<
-        T: Component + PartialEq + Clone + 'static,
-        D: AppDelegate + Dispatcher<Message> + 'static,
-    > Renderable for ComponentWrapper<T, D>
-{
+impl<T: Component + PartialEq + Clone + 'static, B: Backend> Renderable for ComponentWrapper<T, B> {
     fn copy(&self) -> Rc<dyn Renderable> {
-        let wrapper: ComponentWrapper<T, D> = ComponentWrapper {
-            props: Rc::clone(&self.props),
-            state: Rc::clone(&self.state),
-            click_handlers: Rc::clone(&self.click_handlers),
-            change_handlers: Rc::clone(&self.change_handlers),
-            select_handlers: Rc::clone(&self.select_handlers),
-            vdom: Rc::clone(&self.vdom),
-            sub_views: Rc::clone(&self.sub_views),
-            parent_view: RefCell::new(self.parent_view.borrow().clone_as_handle()),
-            component: PhantomData,
-            app: PhantomData,
-        };
-        Rc::new(wrapper)
+        Rc::new(self.clone())
     }
 
     fn as_any(&self) -> &dyn Any {
         self
     }
-    fn equal_to(&self, other: &dyn Renderable) -> bool {
-        other
-            .as_any()
-            .downcast_ref::<Self>()
-            .map(|rhs| self.props == rhs.props)
-            .unwrap_or(false)
-    }
-    fn same_component_as(&self, other: &dyn Renderable) -> bool {
-        other.as_any().is::<Self>()
-    }
-    fn update_props_from(&self, other: Rc<dyn Renderable>) {
+    fn update_props_from(&self, props: &dyn Any) {
         self.update_props(
-            other
-                .as_any()
-                .downcast_ref::<Self>()
-                .unwrap()
-                .props
-                .borrow()
+            props
+                .downcast_ref::<T::Props>()
+                .expect("Props belong to a different component")
                 .clone(),
         );
     }
 
     fn render(&self) {
-        let vdom = T::render(&*self.props.borrow(), &*self.state.borrow());
-        let keys_to_render = vdom.iter().map(|(key, _)| *key).collect::<Vec<_>>();
-        let changes = vdom
-            .into_iter()
-            .flat_map(|(key, node)| {
-                let vdom = self.vdom.borrow();
-                let existing_component = vdom.get(&key);
-                let changes = match existing_component {
-                    Some(existing_component) => self.diff_nodes(existing_component, node),
-                    None => vec![VDomDiff::InsertNode(node)],
-                };
-                changes
-                    .into_iter()
-                    .map(|change| (key, change))
-                    .collect::<Vec<_>>()
-            })
-            .collect::<Vec<_>>();
-        for (key, change) in changes {
-            let mut sub_views = self.sub_views.borrow_mut();
-            let mut vdom = self.vdom.borrow_mut();
-            match change {
-                VDomDiff::InsertNode(mut node) => {
-                    let view = self.create_component(&mut node);
-                    self.parent_view.borrow().add_subview(view.as_layout());
-                    sub_views.insert(key, view);
-                    vdom.insert(key, node);
-                }
-                VDomDiff::ReplaceWith(mut node) => {
-                    vdom.remove(&key);
-                    sub_views
-                        .remove(&key)
-                        .unwrap()
-                        .as_layout()
-                        .remove_from_superview();
-                    let view = self.create_component(&mut node);
-                    self.parent_view.borrow().add_subview(view.as_layout());
-                    sub_views.insert(key, view);
-                    vdom.insert(key, node);
-                }
-                VDomDiff::UpdateLabelText(text) => {
-                    let node = vdom.get_mut(&key).unwrap();
-                    let label = sub_views.get_mut(&key).unwrap();
-                    label.as_label().unwrap().set_text(&text);
-                    node.as_label_mut().unwrap().text = text;
-                }
-                VDomDiff::UpdatePureText(text) => {
-                    let node = vdom.get_mut(&key).unwrap();
-                    let label = sub_views.get_mut(&key).unwrap();
-                    label.as_label().unwrap().set_text(text);
-                    *node.as_text_mut().unwrap() = text;
-                }
-                VDomDiff::UpdateButtonText(text) => {
-                    let node = vdom.get_mut(&key).unwrap();
-                    let button = sub_views.get_mut(&key).unwrap();
-                    button.as_button_mut().unwrap().set_text(&text);
-                    node.as_button_mut().unwrap().text = text;
-                }
-                VDomDiff::UpdateButtonClick(handler) => {
-                    let node = vdom.get_mut(&key).unwrap();
-                    let button = sub_views.get_mut(&key).unwrap();
-                    node.as_button_mut().unwrap().click = handler;
-                    if let Some(handler) = handler {
-                        let id = gen_id();
-                        self.click_handlers.borrow_mut().insert(id, handler);
-                        button.as_button_mut().unwrap().set_action(move |_| {
-                            App::<D, Message>::dispatch_main(Message::click(id))
-                        });
-                    } else {
-                        button.as_button_mut().unwrap().set_action(|_| {});
-                    }
-                }
-                VDomDiff::UpdateInputChange(handler) => {
-                    let node = vdom.get_mut(&key).unwrap();
-                    let input = sub_views.get_mut(&key).unwrap();
-                    node.as_text_input_mut().unwrap().change = handler;
-                    let id = gen_id();
-                    input
-                        .as_text_field_mut()
-                        .unwrap()
-                        .delegate
-                        .as_mut()
-                        .unwrap()
-                        .id = id;
-                    if let Some(handler) = handler {
-                        self.change_handlers.borrow_mut().insert(id, handler);
-                    }
-                }
-                VDomDiff::UpdatePropsFrom(component) => {
-                    let node = vdom.get_mut(&key).unwrap();
-                    node.as_custom()
-                        .unwrap()
-                        .renderable
-                        .0
-                        .as_ref()
-                        .update_props_from(component.renderable.0);
-                }
-            }
+        self.0.dirty.set(false);
+        let parent_view = self.0.parent_view.borrow();
+        let Some(parent_view) = parent_view.as_ref() else {
+            // Nothing to render into until the backend mounts us
+            return;
+        };
+        let new_vdom = assign_keys(rendering::<T, _>(&self.0.receiver, || {
+            T::render(&*self.0.props.borrow(), &*self.0.state.borrow())
+        }));
+        let focused = self.0.sub_views.borrow().focused_path().map(|path| {
+            let positions = positions(&self.0.vdom.borrow(), &path);
+            (path, positions)
+        });
+        let patches = reconcile(&self.0.vdom.borrow(), new_vdom);
+        self.apply_patches(
+            parent_view,
+            &mut self.0.vdom.borrow_mut(),
+            &mut self.0.sub_views.borrow_mut(),
+            patches,
+        );
+        if let Some((path, positions)) = focused {
+            self.restore_focus(parent_view, &path, &positions)
         }
-        let mut vdom = self.vdom.borrow_mut();
-        let keys_to_remove = vdom
-            .keys()
-            .filter(|key| !keys_to_render.contains(key))
-            .copied()
-            .collect::<Vec<_>>();
-        let mut sub_views = self.sub_views.borrow_mut();
-        for key in keys_to_remove {
-            vdom.remove(&key);
-            if let Some(x) = sub_views.remove(&key) {
-                x.as_layout().remove_from_superview()
-            }
+        let autofocus = self.0.sub_views.borrow_mut().take_autofocus();
+        if let Some(path) = autofocus {
+            self.focus_after_flush(move |_| Some(path))
         }
-        let views_to_render = keys_to_render
-            .iter()
-            .map(|key| sub_views.get(key).unwrap().as_layout())
-            .collect::<Vec<_>>();
-        LayoutConstraint::activate(&top_to_bottom(
-            views_to_render,
-            &self.parent_view.borrow().safe_layout_guide,
-            8.,
-        ));
+        self.update_windows();
+        self.update_menu_bar();
+        let props = self.0.props.borrow();
+        let state = self.0.state.borrow();
+        self.update_subscriptions(&props, &state);
+        match self
+            .0
+            .rendered
+            .replace(Some((props.clone(), state.clone())))
+        {
+            None => T::mounted(&props, &state),
+            Some((old_props, old_state)) => T::updated(&old_props, &old_state, &props, &state),
+        }
+    }
+
+    fn will_unmount(&self) {
+        T::will_unmount(&self.0.props.borrow(), &self.0.state.borrow());
+        self.0.cancel_tasks();
+        self.0.subscriptions.take();
+        for (_, node) in self.0.vdom.borrow().iter() {
+            will_unmount(node)
+        }
+        for window in self.0.windows.take() {
+            Self::close_window(window)
+        }
+        self.0.menu_bar.take();
+        set_menu_bar::<B>(self.owner_id(), Vec::new());
     }
 
-    fn set_parent_view(&self, view: View) {
-        *self.parent_view.borrow_mut() = view
+    fn set_depth(&self, depth: usize) {
+        self.0.depth.set(depth)
+    }
+
+    fn set_parent_view(&self, view: Box<dyn Any>) {
+        let view = *view
+            .downcast::<B::Container>()
+            .expect("Parent view belongs to a different backend");
+        // Key presses in the view that no node handles go to the components shortcuts
+        let id = self.0.key_route.get().unwrap_or_else(|| {
+            let id = gen_id();
+            let instance = Rc::downgrade(&self.0);
+            add_route(
+                id,
+                Rc::new(move |message| {
+                    if let Some(instance) = instance.upgrade() {
+                        ComponentWrapper(instance).handle(message)
+                    }
+                }),
+            );
+            self.0.key_route.set(Some(id));
+            id
+        });
+        B::set_container_key_handler(&view, id);
+        *self.0.parent_view.borrow_mut() = Some(view);
+    }
+
+    fn set_key_parent(&self, parent: KeyParent) {
+        *self.0.key_parent.borrow_mut() = Some(parent)
     }
 
     fn on_message(&self, message: &Message) {
         self.on_message(message)
     }
-}
-
-pub enum CacaoComponent<T: Component + PartialEq, D: AppDelegate + Dispatcher<Message>> {
-    Label(Label),
-    Button(Button),
-    View(View<RenderableWrapper>),
-    TextField(TextField<TextInput<D>>),
-    List(ListView<MyListView<T, D>>),
-    Select(Select),
-}
 
-impl<T: Component + Clone + PartialEq, D: AppDelegate + Dispatcher<Message>> CacaoComponent<T, D> {
-    pub fn as_label(&self) -> Option<&Label> {
-        if let Self::Label(v) = self {
-            Some(v)
-        } else {
-            None
-        }
+    fn name(&self) -> &'static str {
+        short_type_name(type_name::<T>())
     }
 
-    pub fn as_label_mut(&mut self) -> Option<&mut Label> {
-        if let Self::Label(v) = self {
-            Some(v)
-        } else {
-            None
+    fn write_snapshot(&self, depth: usize, out: &mut String) {
+        for (key, node) in self.0.vdom.borrow().iter() {
+            write_node(
+                node,
+                Some(key),
+                &*self.0.props.borrow(),
+                &*self.0.state.borrow(),
+                depth,
+                out,
+            );
         }
     }
+}
 
-    pub fn as_button(&self) -> Option<&Button> {
-        if let Self::Button(v) = self {
-            Some(v)
-        } else {
-            None
+impl<T: Component + PartialEq, B: Backend> Instance<T, B> {
+    fn cancel_tasks(&self) {
+        for id in self.tasks.take() {
+            cancel(id)
         }
     }
+}
 
-    pub fn as_button_mut(&mut self) -> Option<&mut Button> {
-        if let Self::Button(v) = self {
-            Some(v)
-        } else {
-            None
+impl<T: Component + PartialEq, B: Backend> Drop for Instance<T, B> {
+    fn drop(&mut self) {
+        self.cancel_tasks();
+        if let Some(id) = self.key_route.get() {
+            remove_route(id)
         }
     }
+}
 
-    pub fn as_view(&self) -> Option<&View<RenderableWrapper>> {
-        if let Self::View(v) = self {
-            Some(v)
-        } else {
-            None
-        }
+/// The siblings inside the container at the end of a path of keys, or the top level nodes for an empty path
+fn siblings_at<'a, T: Component>(vdom: &'a VDom<T>, path: &[Key]) -> Option<&'a VDom<T>> {
+    let Some((outer, rest)) = path.split_first() else {
+        return Some(vdom);
+    };
+    let (_, node) = vdom.iter().find(|(key, _)| key == outer)?;
+    match node.content() {
+        VNode::Container(container) => siblings_at(&container.children, rest),
+        _ => None,
     }
+}
 
-    pub fn as_view_mut(&mut self) -> Option<&mut View<RenderableWrapper>> {
-        if let Self::View(v) = self {
-            Some(v)
-        } else {
-            None
+/// Where each node along a path of keys sits among its siblings
+fn positions<T: Component>(vdom: &VDom<T>, path: &[Key]) -> Vec<usize> {
+    (0..path.len())
+        .map(|depth| {
+            siblings_at(vdom, &path[..depth])
+                .and_then(|siblings| siblings.iter().position(|(key, _)| *key == path[depth]))
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Runs `Component::will_unmount` for every component in a node that is about to be removed
+fn will_unmount<T: Component + ?Sized>(node: &VNode<T>) {
+    match node {
+        VNode::Custom(component) => component.renderable().will_unmount(),
+        VNode::Container(container) => {
+            for (_, child) in &container.children {
+                will_unmount(child)
+            }
         }
+        VNode::Keyed(_, node) | VNode::With(_, node) => will_unmount(node),
+        _ => {}
     }
+}
 
-    pub fn as_layout(&self) -> &dyn Layout {
-        match self {
-            CacaoComponent::Label(label) => label,
-            CacaoComponent::Button(button) => button,
-            CacaoComponent::View(view) => view,
-            CacaoComponent::TextField(text_input) => text_input,
-            CacaoComponent::List(list) => list,
-            CacaoComponent::Select(select) => select,
-        }
+impl<T, B> Scheduled for Instance<T, B>
+where
+    T: Component + PartialEq + Clone + 'static,
+    B: Backend,
+{
+    fn depth(&self) -> usize {
+        self.depth.get()
     }
 
-    pub fn as_text_field(&self) -> Option<&TextField<TextInput<D>>> {
-        if let Self::TextField(v) = self {
-            Some(v)
-        } else {
-            None
+    fn render_if_dirty(self: Rc<Self>) {
+        if self.dirty.get() {
+            ComponentWrapper(self).render()
         }
     }
+}
 
-    pub fn as_text_field_mut(&mut self) -> Option<&mut TextField<TextInput<D>>> {
-        if let Self::TextField(v) = self {
-            Some(v)
-        } else {
-            None
-        }
+/// What the backend is given to build a menu from
+fn native_menu<T: Component>(menu: &VMenu<T>, ids: &MenuIds) -> NativeMenu {
+    NativeMenu {
+        title: menu.title.clone(),
+        items: menu
+            .items
+            .iter()
+            .zip(ids)
+            .map(|(item, id)| NativeMenuItem {
+                title: item.title.clone(),
+                key_equivalent: item.key_equivalent.clone(),
+                enabled: item.enabled,
+                checked: item.checked,
+                id: id.as_ref().map(|(id, _)| *id),
+            })
+            .collect(),
     }
 }
 
@@ -727,30 +1784,25 @@ pub enum VDomDiff<T: Component> {
     UpdateButtonText(String),
     UpdateButtonClick(Option<ClickHandler<T>>),
     UpdateInputChange(Option<ChangeHandler<T>>),
+    UpdateInputValue(String),
+    UpdateSelectOptions(Vec<String>),
+    UpdateSelection(Option<usize>),
+    UpdateSelectHandler(Option<SelectHandler<T>>),
+    /// Renders every row of a list again, with its count and render function as they are now
+    UpdateList(VList<T>),
     UpdatePropsFrom(VComponent),
+    /// Adds, changes or takes away the context menu of a node
+    UpdateContextMenu(Option<VMenu<T>>),
+    UpdateKeyDown(Option<KeyDownHandler<T>>),
+    /// Sets the title of a box
+    UpdateTitle(String),
+    /// Changes to the children of a container, which are keyed separately from the containers siblings
+    UpdateChildren(Vec<Patch<T>>),
     InsertNode(VNode<T>),
     ReplaceWith(VNode<T>),
-}
-
-pub struct TextInput<App: AppDelegate> {
-    id: usize,
-    app: PhantomData<App>,
-}
-
-impl<App: AppDelegate> TextInput<App> {
-    pub fn new(id: usize) -> Self {
-        Self {
-            id,
-            app: PhantomData,
-        }
-    }
-}
-
-impl<D: AppDelegate + Dispatcher<Message>> TextFieldDelegate for TextInput<D> {
-    const NAME: &'static str = "TextInput";
-    fn text_did_change(&self, value: &str) {
-        App::<D, Message>::dispatch_main(Message::change(self.id, value.to_owned()));
-    }
+    Remove,
+    /// The node is moved in front of the sibling given by its `Patch`
+    Move,
 }
 
 #[derive(PartialEq, Debug)]
@@ -764,29 +1816,47 @@ pub enum Payload {
     Click,
     Change(String),
     Select(usize),
+    /// The user closed a window
+    Close,
+    /// A key was pressed in a widget or the view of a component
+    Key(KeyPress),
     Custom(Box<dyn Any + Send + Sync>),
 }
 
 impl Message {
-    fn click(id: usize) -> Self {
+    pub(crate) fn click(id: usize) -> Self {
         Self {
             id,
             payload: Payload::Click,
         }
     }
-    fn change(id: usize, value: String) -> Self {
+    pub(crate) fn change(id: usize, value: String) -> Self {
         Self {
             id,
             payload: Payload::Change(value),
         }
     }
-    fn select(id: usize, value: usize) -> Self {
+    pub(crate) fn select(id: usize, value: usize) -> Self {
         Self {
             id,
             payload: Payload::Select(value),
         }
     }
 
+    pub(crate) fn close(id: usize) -> Self {
+        Self {
+            id,
+            payload: Payload::Close,
+        }
+    }
+
+    pub(crate) fn key(id: usize, press: KeyPress) -> Self {
+        Self {
+            id,
+            payload: Payload::Key(press),
+        }
+    }
+
     pub fn custom(message: impl Any + Send + Sync) -> Self {
         Self {
             // This is a bit silly but for now it needs an id and we don't want one that  will conflict with something else
@@ -802,11 +1872,491 @@ impl PartialEq for Payload {
         match (self, other) {
             (Self::Click, Self::Click) => true,
             (Self::Change(a), Self::Change(b)) => a == b,
+            (Self::Close, Self::Close) => true,
+            (Self::Key(a), Self::Key(b)) => a == b,
             (Self::Custom(_), Self::Custom(_)) => false,
             _ => false,
         }
     }
 }
 
-#[derive(Clone)]
-pub struct RenderableWrapper(Rc<dyn Renderable>);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ClickHandler, Command, Component, HeadlessBackend, HeadlessEvent, HeadlessNode, KeyValue,
+        TestHarness, VButton, VComponent, VLabel, VList, VNode, VSelect, VTextInput, WidgetKind,
+    };
+
+    #[derive(PartialEq, Clone)]
+    struct Picker;
+
+    impl Component for Picker {
+        type Props = ();
+        type State = usize;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, count| *count += 1)),
+                    text: "More".to_string(),
+                }),
+                VNode::TextInput(VTextInput {
+                    change: None,
+                    initial_value: state.to_string(),
+                }),
+                VNode::Select(VSelect {
+                    options: (0..=*state).map(|option| option.to_string()).collect(),
+                    selected: Some(*state),
+                    select: None,
+                }),
+                VNode::List(VList {
+                    count: *state,
+                    render: |index, _, _| {
+                        vec![VNode::Text(if index == 0 { "first" } else { "next" })]
+                    },
+                }),
+            ]
+        }
+    }
+
+    #[test]
+    fn updates_inputs_selects_and_lists_in_place() {
+        let harness = TestHarness::<Picker>::new(());
+        let before = harness
+            .root()
+            .children()
+            .iter()
+            .map(HeadlessNode::id)
+            .collect::<Vec<_>>();
+        HeadlessBackend::take_events();
+        harness.click(&harness.find_button("More"));
+        let after = harness.root().children();
+        assert_eq!(
+            after.iter().map(HeadlessNode::id).collect::<Vec<_>>(),
+            before
+        );
+        assert_eq!(after[1].text(), "1");
+        assert_eq!(after[2].borrow().options, ["0", "1"]);
+        assert_eq!(after[2].borrow().selected, Some(1));
+        assert_eq!(after[3].children().len(), 1);
+        let created = HeadlessBackend::take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                HeadlessEvent::Create { kind, .. } => Some(kind),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Only the new list row is created
+        assert_eq!(created, [WidgetKind::Container, WidgetKind::Label]);
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Rows;
+
+    impl Component for Rows {
+        type Props = ();
+        type State = usize;
+        fn render(_props: &Self::Props, picked: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Label(VLabel {
+                    text: format!("Picked {picked}"),
+                }),
+                VNode::List(VList {
+                    count: 3,
+                    render: |i, _, picked| {
+                        // Captured so a click only picks the right value if the handler is rebound when the state changes
+                        let next = picked + i + 10;
+                        vec![
+                            VNode::Button(VButton {
+                                click: Some(
+                                    ClickHandler::<Rows>::new(move |_, s| *s = next).deps(next),
+                                ),
+                                text: format!("Row {i}"),
+                            }),
+                            VNode::Label(VLabel {
+                                text: format!("Row {i} with {picked} picked"),
+                            }),
+                        ]
+                    },
+                }),
+            ]
+        }
+    }
+
+    #[test]
+    fn renders_list_rows_again_when_the_state_changes() {
+        let harness = TestHarness::<Rows>::new(());
+        harness.find_label_by_text("Row 2 with 0 picked");
+        harness.click(&harness.find_button("Row 1"));
+        harness.find_label_by_text("Row 2 with 11 picked");
+    }
+
+    #[test]
+    fn routes_clicks_in_list_rows_to_the_component() {
+        let harness = TestHarness::<Rows>::new(());
+        harness.click(&harness.find_button("Row 1"));
+        assert_eq!(harness.root().children()[0].text(), "Picked 11");
+        harness.click(&harness.find_button("Row 2"));
+        assert_eq!(harness.root().children()[0].text(), "Picked 23");
+        harness.click(&harness.find_button("Row 2"));
+        assert_eq!(harness.root().children()[0].text(), "Picked 35");
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Sorter;
+
+    impl Component for Sorter {
+        type Props = ();
+        type State = bool;
+        fn render(_props: &Self::Props, reversed: &Self::State) -> Vec<VNode<Self>> {
+            let mut nodes = ["a", "b", "c", "d"]
+                .into_iter()
+                .enumerate()
+                .map(|(key, text)| VNode::Text(text).key(key))
+                .collect::<Vec<_>>();
+            if *reversed {
+                nodes.reverse();
+            }
+            nodes.push(
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, reversed: &mut bool| {
+                        *reversed = !*reversed
+                    })),
+                    text: "Reverse".to_string(),
+                })
+                .key(4),
+            );
+            nodes
+        }
+    }
+
+    #[test]
+    fn reorders_existing_widgets() {
+        let harness = TestHarness::<Sorter>::new(());
+        let ids = harness
+            .root()
+            .children()
+            .iter()
+            .map(HeadlessNode::id)
+            .collect::<Vec<_>>();
+        HeadlessBackend::take_events();
+        harness.click(&harness.find_button("Reverse"));
+        let children = harness.root().children();
+        let texts = children.iter().map(HeadlessNode::text).collect::<Vec<_>>();
+        assert_eq!(texts, ["d", "c", "b", "a", "Reverse"]);
+        assert_eq!(
+            children.iter().map(HeadlessNode::id).collect::<Vec<_>>(),
+            [ids[3], ids[2], ids[1], ids[0], ids[4]]
+        );
+        let moves = HeadlessBackend::take_events()
+            .into_iter()
+            .filter(|event| matches!(event, HeadlessEvent::MoveChild { .. }))
+            .count();
+        assert_eq!(moves, 3);
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Counter;
+
+    impl Component for Counter {
+        type Props = ();
+        type State = usize;
+        fn render(_props: &Self::Props, count: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::titled_box(
+                format!("Clicked {count} times"),
+                vec![
+                    VNode::group(vec![VNode::Button(VButton {
+                        click: Some(ClickHandler::<Self>::new(|_, count| *count += 1)),
+                        text: "Click".to_string(),
+                    })]),
+                    VNode::Label(VLabel {
+                        text: count.to_string(),
+                    }),
+                ],
+            )]
+        }
+    }
+
+    #[test]
+    fn patches_children_of_containers_in_place() {
+        let harness = TestHarness::<Counter>::new(());
+        let before = harness
+            .root()
+            .descendants()
+            .iter()
+            .map(HeadlessNode::id)
+            .collect::<Vec<_>>();
+        harness.click(&harness.find_button("Click"));
+        let after = harness.root().descendants();
+        assert_eq!(
+            after.iter().map(HeadlessNode::id).collect::<Vec<_>>(),
+            before
+        );
+        assert_eq!(
+            after.iter().map(HeadlessNode::kind).collect::<Vec<_>>(),
+            [
+                WidgetKind::Container,
+                WidgetKind::Box,
+                WidgetKind::Group,
+                WidgetKind::Button,
+                WidgetKind::Label
+            ]
+        );
+        assert_eq!(after[1].text(), "Clicked 1 times");
+        assert_eq!(after[4].text(), "1");
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Stepper;
+
+    impl Component for Stepper {
+        type Props = ();
+        type State = usize;
+        fn render(_props: &Self::Props, count: &Self::State) -> Vec<VNode<Self>> {
+            if *count == 3 {
+                return vec![VNode::Text("Done")];
+            }
+            let next = count + 1;
+            vec![
+                VNode::Label(VLabel {
+                    text: count.to_string(),
+                }),
+                VNode::group(vec![VNode::Button(VButton {
+                    // Capturing `next` without dependencies rebinds the handler every render
+                    click: Some(ClickHandler::<Self>::new(move |_, count| *count = next)),
+                    text: "Step".to_string(),
+                })]),
+            ]
+        }
+    }
+
+    #[test]
+    fn unregisters_handlers_of_rebound_and_removed_nodes() {
+        let harness = TestHarness::<Stepper>::new(());
+        let first = harness.find_button("Step").handler().unwrap();
+        harness.click(&harness.find_button("Step"));
+        harness.click(&harness.find_button("Step"));
+        assert_eq!(harness.component().handler_count(), 1);
+        harness.send(Message::click(first));
+        harness.find_label_by_text("2");
+        harness.click(&harness.find_button("Step"));
+        harness.find_label_by_text("Done");
+        assert_eq!(harness.component().handler_count(), 0);
+    }
+
+    thread_local! {
+        static LIFECYCLE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Probe;
+
+    impl Component for Probe {
+        type Props = u32;
+        type State = ();
+        fn render(props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::Label(VLabel {
+                text: props.to_string(),
+            })]
+        }
+        fn mounted(props: &Self::Props, _state: &Self::State) {
+            LIFECYCLE.with(|log| log.borrow_mut().push(format!("mounted {props}")))
+        }
+        fn updated(old_props: &u32, _old_state: &(), props: &u32, _state: &()) {
+            LIFECYCLE.with(|log| {
+                log.borrow_mut()
+                    .push(format!("updated {old_props} to {props}"))
+            })
+        }
+        fn will_unmount(props: &Self::Props, _state: &Self::State) {
+            LIFECYCLE.with(|log| log.borrow_mut().push(format!("will_unmount {props}")))
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Toggle;
+
+    impl Component for Toggle {
+        type Props = ();
+        type State = (bool, u32);
+        fn render(_props: &Self::Props, (shown, count): &Self::State) -> Vec<VNode<Self>> {
+            let mut nodes = vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, (shown, _)| *shown = !*shown)),
+                    text: "Toggle".to_string(),
+                }),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, (_, count)| *count += 1)),
+                    text: "Add".to_string(),
+                }),
+            ];
+            if *shown {
+                nodes.push(VNode::group(vec![VNode::Custom(VComponent::new::<Probe>(
+                    *count,
+                ))]));
+            }
+            nodes
+        }
+    }
+
+    #[test]
+    fn runs_lifecycle_hooks() {
+        let harness = TestHarness::<Toggle>::new(());
+        harness.click(&harness.find_button("Toggle"));
+        harness.click(&harness.find_button("Add"));
+        harness.click(&harness.find_button("Toggle"));
+        assert_eq!(
+            LIFECYCLE.with(|log| log.take()),
+            ["mounted 0", "updated 0 to 1", "will_unmount 1"]
+        );
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Todos;
+
+    #[derive(PartialEq, Clone)]
+    struct TodosState {
+        items: Vec<&'static str>,
+        adding: bool,
+    }
+
+    impl Default for TodosState {
+        fn default() -> Self {
+            Self {
+                items: vec!["A", "B", "C"],
+                adding: false,
+            }
+        }
+    }
+
+    impl Component for Todos {
+        type Props = ();
+        type State = TodosState;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::group(
+                    state
+                        .items
+                        .iter()
+                        .map(|&item| {
+                            VNode::Button(VButton {
+                                click: Some(
+                                    ClickHandler::<Self>::new(move |_, state: &mut TodosState| {
+                                        state.items.retain(|other| *other != item)
+                                    })
+                                    .deps(item),
+                                ),
+                                text: item.to_string(),
+                            })
+                            .key(item)
+                        })
+                        .collect(),
+                )
+                .key("items"),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, state: &mut TodosState| {
+                        state.items.reverse()
+                    })),
+                    text: "Reverse".to_string(),
+                }),
+                if state.adding {
+                    VNode::TextInput(VTextInput {
+                        change: None,
+                        initial_value: String::new(),
+                    })
+                    .autofocus()
+                } else {
+                    VNode::Button(VButton {
+                        click: Some(ClickHandler::<Self>::new(|_, state: &mut TodosState| {
+                            state.adding = true
+                        })),
+                        text: "Add".to_string(),
+                    })
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn focuses_autofocus_nodes_when_they_are_created() {
+        let harness = TestHarness::<Todos>::new(());
+        assert!(harness.focused().is_none());
+        harness.click(&harness.find_button("Add"));
+        let input = harness.find_text_input("");
+        assert_eq!(harness.focused().map(|node| node.id()), Some(input.id()));
+    }
+
+    #[test]
+    fn keeps_focus_on_nodes_that_are_moved() {
+        let harness = TestHarness::<Todos>::new(());
+        let c = harness.find_button("C");
+        harness.focus(&c);
+        // C is moved to the front, which takes focus away from it natively
+        harness.click(&harness.find_button("Reverse"));
+        assert_eq!(
+            harness
+                .find_all(WidgetKind::Button)
+                .iter()
+                .take(3)
+                .map(HeadlessNode::text)
+                .collect::<Vec<_>>(),
+            ["C", "B", "A"]
+        );
+        assert_eq!(harness.focused().map(|node| node.id()), Some(c.id()));
+    }
+
+    #[test]
+    fn moves_focus_to_a_neighbour_when_the_focused_node_is_removed() {
+        let harness = TestHarness::<Todos>::new(());
+        let b = harness.find_button("B");
+        harness.focus(&b);
+        harness.click(&b);
+        // The node that took its place
+        let c = harness.focused().unwrap();
+        assert_eq!(c.text(), "C");
+        harness.click(&c);
+        // The one before it once it was the last
+        let a = harness.focused().unwrap();
+        assert_eq!(a.text(), "A");
+        harness.click(&a);
+        // The container once there is nothing left in it
+        assert_eq!(harness.focused().unwrap().kind(), WidgetKind::Group);
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Sections;
+
+    impl Component for Sections {
+        type Props = ();
+        type State = ();
+        fn render(_props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            let section = |title: &'static str| {
+                VNode::titled_box(
+                    title,
+                    vec![VNode::Label(VLabel {
+                        text: format!("{title} name"),
+                    })
+                    .key("name")],
+                )
+                .key(title)
+            };
+            vec![
+                section("First"),
+                section("Second"),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, _| {
+                        Command::focus_path([KeyValue::new("Second"), KeyValue::new("name")])
+                    })),
+                    text: "Rename second".to_string(),
+                }),
+            ]
+        }
+    }
+
+    #[test]
+    fn focuses_nodes_by_their_path_through_containers() {
+        let harness = TestHarness::<Sections>::new(());
+        harness.click(&harness.find_button("Rename second"));
+        assert_eq!(harness.focused().unwrap().text(), "Second name");
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Wake, Waker},
+};
+
+use crate::Backend;
+
+struct Task {
+    /// Taken out while the task is being polled
+    future: Option<Pin<Box<dyn Future<Output = ()>>>>,
+    /// `Backend::schedule_poll` of the backend the task was spawned with
+    schedule: fn(),
+}
+
+/// The ids of woken tasks, shared with wakers that may be on other threads
+type Ready = Arc<Mutex<VecDeque<usize>>>;
+
+thread_local! {
+    /// Futures run on the thread that spawned them, which for components is the main thread
+    static TASKS: RefCell<HashMap<usize, Task>> = RefCell::new(HashMap::new());
+    static READY: Ready = Ready::default();
+}
+
+struct TaskWaker {
+    id: usize,
+    ready: Ready,
+    schedule: fn(),
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+        (self.schedule)()
+    }
+}
+
+/// Runs a future on this thread, returning an id that can cancel it
+pub(crate) fn spawn<B: Backend>(future: impl Future<Output = ()> + 'static) -> usize {
+    static NEXT_TASK: AtomicUsize = AtomicUsize::new(0);
+    let id = NEXT_TASK.fetch_add(1, Ordering::Relaxed);
+    let task = Task {
+        future: Some(Box::pin(future)),
+        schedule: B::schedule_poll,
+    };
+    TASKS.with(|tasks| tasks.borrow_mut().insert(id, task));
+    READY.with(|ready| ready.lock().unwrap().push_back(id));
+    B::schedule_poll();
+    id
+}
+
+/// Drops the future of a task that hasn't finished
+pub(crate) fn cancel(id: usize) {
+    // Components can be dropped as the thread exits
+    let _ = TASKS.try_with(|tasks| tasks.borrow_mut().remove(&id));
+}
+
+pub(crate) fn is_running(id: usize) -> bool {
+    TASKS.with(|tasks| tasks.borrow().contains_key(&id))
+}
+
+/// Polls the tasks that have been woken since the last call in the order they were woken, returning whether there were any.
+/// Backends call this when asked to by `Backend::schedule_poll`, headless tests call it to step through tasks deterministically
+pub fn poll_tasks() -> bool {
+    let mut polled = false;
+    while let Some(id) = READY.with(|ready| ready.lock().unwrap().pop_front()) {
+        // Taken out while it is polled so the task can spawn and cancel others
+        let Some((mut future, schedule)) = TASKS.with(|tasks| {
+            let mut tasks = tasks.borrow_mut();
+            let task = tasks.get_mut(&id)?;
+            Some((task.future.take()?, task.schedule))
+        }) else {
+            // Cancelled, finished or being polled by a call further up the stack
+            continue;
+        };
+        polled = true;
+        let waker = Waker::from(Arc::new(TaskWaker {
+            id,
+            ready: READY.with(Arc::clone),
+            schedule,
+        }));
+        let finished = future
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready();
+        TASKS.with(|tasks| {
+            let mut tasks = tasks.borrow_mut();
+            if finished {
+                tasks.remove(&id);
+            } else if let Some(task) = tasks.get_mut(&id) {
+                // Unless it was cancelled while it was polled
+                task.future = Some(future);
+            }
+        });
+    }
+    polled
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        future::poll_fn,
+        rc::Rc,
+        task::{Poll, Waker},
+    };
+
+    use crate::{
+        poll_tasks, ClickHandler, Command, Component, TestHarness, VButton, VComponent, VLabel,
+        VNode,
+    };
+
+    /// A reply the test gives to a task by hand
+    #[derive(Default)]
+    struct Reply {
+        value: Option<String>,
+        waker: Option<Waker>,
+    }
+
+    thread_local! {
+        static REPLIES: RefCell<Vec<Rc<RefCell<Reply>>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    async fn fetch() -> String {
+        let reply = Rc::new(RefCell::new(Reply::default()));
+        REPLIES.with(|replies| replies.borrow_mut().push(reply.clone()));
+        poll_fn(|context| {
+            let mut reply = reply.borrow_mut();
+            match reply.value.take() {
+                Some(value) => Poll::Ready(value),
+                None => {
+                    reply.waker = Some(context.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Loader;
+
+    #[derive(PartialEq, Clone)]
+    enum LoaderMessage {
+        Loaded(String),
+    }
+
+    impl Component for Loader {
+        type Props = ();
+        type State = Option<String>;
+        type Message = LoaderMessage;
+        fn render(_props: &Self::Props, loaded: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, _| {
+                        Command::spawn(fetch(), LoaderMessage::Loaded)
+                    })),
+                    text: "Load".to_string(),
+                }),
+                VNode::Label(VLabel {
+                    text: loaded.clone().unwrap_or_else(|| "Waiting".to_string()),
+                }),
+            ]
+        }
+        fn on_message(
+            LoaderMessage::Loaded(value): &LoaderMessage,
+            _props: &Self::Props,
+            loaded: &mut Self::State,
+        ) -> bool {
+            *loaded = Some(value.clone());
+            true
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Host;
+
+    impl Component for Host {
+        type Props = ();
+        type State = bool;
+        fn render(_props: &Self::Props, hidden: &Self::State) -> Vec<VNode<Self>> {
+            let mut nodes = vec![VNode::Button(VButton {
+                click: Some(ClickHandler::<Self>::new(|_, hidden: &mut bool| {
+                    *hidden = true
+                })),
+                text: "Hide".to_string(),
+            })];
+            if !hidden {
+                nodes.push(VNode::Custom(VComponent::new::<Loader>(())));
+            }
+            nodes
+        }
+    }
+
+    #[test]
+    fn delivers_task_results_as_messages() {
+        let harness = TestHarness::<Host>::new(());
+        harness.click(&harness.find_button("Load"));
+        harness.find_label_by_text("Waiting");
+        let reply = REPLIES.with(|replies| replies.borrow_mut().remove(0));
+        reply.borrow_mut().value = Some("Loaded".to_string());
+        reply.borrow_mut().waker.take().unwrap().wake();
+        harness.settle();
+        harness.find_label_by_text("Loaded");
+        assert!(!poll_tasks());
+    }
+
+    #[test]
+    fn cancels_tasks_when_their_component_is_unmounted() {
+        let harness = TestHarness::<Host>::new(());
+        harness.click(&harness.find_button("Load"));
+        let reply = REPLIES.with(|replies| replies.borrow_mut().remove(0));
+        assert_eq!(Rc::strong_count(&reply), 2);
+        harness.click(&harness.find_button("Hide"));
+        // Only the test holds the reply once the task has been dropped
+        assert_eq!(Rc::strong_count(&reply), 1);
+    }
+}
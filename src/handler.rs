@@ -0,0 +1,362 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    cell::RefCell,
+    fmt::Debug,
+    hash::Hash,
+    mem::size_of,
+    ops::Deref,
+    rc::{Rc, Weak},
+};
+
+use crate::{routing::queue_event, Command, Component, IntoUpdate, KeyPress, KeyValue};
+
+pub type ClickHandler<T> = Handler<
+    dyn Fn(
+        &<T as Component>::Props,
+        &mut <T as Component>::State,
+    ) -> (bool, Command<<T as Component>::Message>),
+>;
+pub type ChangeHandler<T> = Handler<
+    dyn Fn(
+        &str,
+        &<T as Component>::Props,
+        &mut <T as Component>::State,
+    ) -> (bool, Command<<T as Component>::Message>),
+>;
+pub type SelectHandler<T> = Handler<
+    dyn Fn(
+        usize,
+        &<T as Component>::Props,
+        &mut <T as Component>::State,
+    ) -> (bool, Command<<T as Component>::Message>),
+>;
+pub type KeyDownHandler<T> = Handler<
+    dyn Fn(
+        &KeyPress,
+        &<T as Component>::Props,
+        &mut <T as Component>::State,
+    ) -> Option<(bool, Command<<T as Component>::Message>)>,
+>;
+
+/// An event handler built from a closure, so it can capture things like the index of the row it was rendered in.
+/// A new closure is made every render so handlers are compared by where they came from instead:
+/// - Closures that capture nothing are equal when they come from the same closure expression.
+/// - Closures given dependencies with `deps` are equal when they come from the same closure expression and their dependencies are equal.
+/// - Any other closure is only equal to itself, so it is rebound every render.
+///
+/// Handlers are made with `ClickHandler::<Self>::new(...)` and the like in `Component::render`.
+/// The component has to be named for the closures arguments to be inferred.
+/// The closures can return anything that implements `IntoUpdate` to say whether to render again and what commands to run
+pub struct Handler<F: ?Sized> {
+    callback: Rc<F>,
+    /// Each closure expression has its own type
+    closure: TypeId,
+    captures_nothing: bool,
+    deps: Option<KeyValue>,
+}
+
+impl<F: ?Sized> Handler<F> {
+    fn from_closure<C: 'static>(callback: Rc<F>) -> Self {
+        Self {
+            callback,
+            closure: TypeId::of::<C>(),
+            captures_nothing: size_of::<C>() == 0,
+            deps: None,
+        }
+    }
+
+    /// The values the closure captures, the handler is only rebound when they change
+    pub fn deps(mut self, deps: impl Hash + Eq + Debug + 'static) -> Self {
+        self.deps = Some(KeyValue::new(deps));
+        self
+    }
+}
+
+impl<P: ?Sized, S: ?Sized, M> Handler<dyn Fn(&P, &mut S) -> (bool, Command<M>)> {
+    pub fn new<C, R>(callback: C) -> Self
+    where
+        C: Fn(&P, &mut S) -> R + 'static,
+        R: IntoUpdate<M>,
+    {
+        Self::from_closure::<C>(Rc::new(move |props: &P, state: &mut S| {
+            callback(props, state).into_update()
+        }))
+    }
+}
+
+impl<P: ?Sized, S: ?Sized, M> Handler<dyn Fn(&str, &P, &mut S) -> (bool, Command<M>)> {
+    /// The closure is given the new value
+    pub fn new<C, R>(callback: C) -> Self
+    where
+        C: Fn(&str, &P, &mut S) -> R + 'static,
+        R: IntoUpdate<M>,
+    {
+        Self::from_closure::<C>(Rc::new(move |value: &str, props: &P, state: &mut S| {
+            callback(value, props, state).into_update()
+        }))
+    }
+}
+
+impl<P: ?Sized, S: ?Sized, M> Handler<dyn Fn(usize, &P, &mut S) -> (bool, Command<M>)> {
+    /// The closure is given the index of the chosen option
+    pub fn new<C, R>(callback: C) -> Self
+    where
+        C: Fn(usize, &P, &mut S) -> R + 'static,
+        R: IntoUpdate<M>,
+    {
+        Self::from_closure::<C>(Rc::new(move |index: usize, props: &P, state: &mut S| {
+            callback(index, props, state).into_update()
+        }))
+    }
+}
+
+impl<P: ?Sized, S: ?Sized, M> Handler<dyn Fn(&KeyPress, &P, &mut S) -> Option<(bool, Command<M>)>> {
+    /// The closure is given the key that was pressed.
+    /// Returning `None` leaves the key press to the shortcuts of the component and then those of the components it is nested in
+    pub fn new<C, R>(callback: C) -> Self
+    where
+        C: Fn(&KeyPress, &P, &mut S) -> Option<R> + 'static,
+        R: IntoUpdate<M>,
+    {
+        Self::from_closure::<C>(Rc::new(
+            move |press: &KeyPress, props: &P, state: &mut S| {
+                callback(press, props, state).map(IntoUpdate::into_update)
+            },
+        ))
+    }
+}
+
+impl<F: ?Sized> Deref for Handler<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.callback
+    }
+}
+
+impl<F: ?Sized> Clone for Handler<F> {
+    fn clone(&self) -> Self {
+        Self {
+            callback: Rc::clone(&self.callback),
+            closure: self.closure,
+            captures_nothing: self.captures_nothing,
+            deps: self.deps.clone(),
+        }
+    }
+}
+
+impl<F: ?Sized> PartialEq for Handler<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.closure == other.closure
+            && match (&self.deps, &other.deps) {
+                (Some(a), Some(b)) => a == b,
+                (None, None) => {
+                    self.captures_nothing || Rc::ptr_eq(&self.callback, &other.callback)
+                }
+                _ => false,
+            }
+    }
+}
+
+/// Lets a child component tell the component that rendered it that something happened, like a row being deleted.
+/// The parent makes one in `Component::render` and passes it to the child in its props, for example `Callback::<Self, usize>::new(ListMessage::Delete)`.
+/// Emitting it turns the payload into the parents `Component::Message`, runs the parents `Component::on_message` with its own state and renders only the parent if that returns true.
+/// Callbacks are compared like handlers so passing one in props doesn't make the child render again every time the parent does
+pub struct Callback<T: Component + ?Sized, P> {
+    map: Handler<dyn Fn(P) -> T::Message>,
+    target: Weak<dyn Fn(T::Message)>,
+}
+
+/// Where the callbacks made while a component renders send their messages
+struct Target<T: Component + ?Sized>(Weak<dyn Fn(T::Message)>);
+
+thread_local! {
+    /// The targets of the components currently rendering, innermost last
+    static RENDERING: RefCell<Vec<Box<dyn Any>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Renders a component, pointing any callbacks it makes at `target`
+pub(crate) fn rendering<T: Component + ?Sized + 'static, R>(
+    target: &Rc<dyn Fn(T::Message)>,
+    render: impl FnOnce() -> R,
+) -> R {
+    RENDERING.with(|rendering| {
+        let target: Box<dyn Any> = Box::new(Target::<T>(Rc::downgrade(target)));
+        rendering.borrow_mut().push(target)
+    });
+    let nodes = render();
+    RENDERING.with(|rendering| rendering.borrow_mut().pop());
+    nodes
+}
+
+impl<T: Component + ?Sized + 'static, P: 'static> Callback<T, P> {
+    /// Must be called from the `Component::render` of `T`
+    pub fn new<C: Fn(P) -> T::Message + 'static>(map: C) -> Self {
+        let target = RENDERING.with(|rendering| {
+            rendering
+                .borrow()
+                .last()
+                .and_then(|target| target.downcast_ref::<Target<T>>())
+                .map(|target| target.0.clone())
+        });
+        let Some(target) = target else {
+            panic!(
+                "A Callback<{}, _> can only be made while {0} renders",
+                type_name::<T>()
+            );
+        };
+        Self {
+            map: Handler::from_closure::<C>(Rc::new(map)),
+            target,
+        }
+    }
+
+    /// The values the closure captures, see `Handler::deps`
+    pub fn deps(mut self, deps: impl Hash + Eq + Debug + 'static) -> Self {
+        self.map = self.map.deps(deps);
+        self
+    }
+
+    /// Sends the payload to the component that made the callback.
+    /// Events emitted while another event or handler is running are delivered once it finishes, so the parent can safely update the props of the child that emitted it
+    pub fn emit(&self, payload: P) {
+        let map = self.map.clone();
+        let target = self.target.clone();
+        queue_event(Box::new(move || {
+            if let Some(target) = target.upgrade() {
+                target(map(payload))
+            }
+        }))
+    }
+}
+
+impl<T: Component + ?Sized, P> Clone for Callback<T, P> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            target: self.target.clone(),
+        }
+    }
+}
+
+impl<T: Component + ?Sized, P> PartialEq for Callback<T, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map && Weak::ptr_eq(&self.target, &other.target)
+    }
+}
+
+impl<T: Component + ?Sized, P> Debug for Callback<T, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Callback<{}>", type_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Callback, ClickHandler, Command, Component, HeadlessBackend, HeadlessEvent, TestHarness,
+        VButton, VComponent, VLabel, VNode, WidgetKind,
+    };
+
+    type Click = Handler<dyn Fn(&(), &mut usize) -> (bool, Command<()>)>;
+
+    fn add(amount: usize) -> Click {
+        Click::new(move |_, count| *count += amount)
+    }
+
+    #[test]
+    fn compares_closures_by_where_they_came_from() {
+        let reset = || Click::new(|_, count| *count = 0);
+        assert!(reset() == reset());
+        assert!(reset() != Click::new(|_, count| *count = 0));
+        // Captured values can't be compared so these are never equal without dependencies
+        assert!(add(1) != add(1));
+        assert!(add(1).deps(1) == add(1).deps(1));
+        assert!(add(1).deps(1) != add(2).deps(2));
+        let mut count = 0;
+        add(2)(&(), &mut count);
+        assert_eq!(count, 2);
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Item;
+
+    #[derive(PartialEq, Clone)]
+    struct ItemProps {
+        name: String,
+        on_remove: Callback<Basket, String>,
+    }
+
+    impl Component for Item {
+        type Props = ItemProps;
+        type State = ();
+        fn render(props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::Button(VButton {
+                click: Some(ClickHandler::<Self>::new(|props: &ItemProps, _| {
+                    props.on_remove.emit(props.name.clone())
+                })),
+                text: format!("Remove {}", props.name),
+            })]
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Basket;
+
+    #[derive(PartialEq, Clone)]
+    enum BasketMessage {
+        Remove(String),
+    }
+
+    impl Component for Basket {
+        type Props = Vec<String>;
+        type State = Vec<String>;
+        type Message = BasketMessage;
+        fn render(items: &Self::Props, removed: &Self::State) -> Vec<VNode<Self>> {
+            let mut nodes = items
+                .iter()
+                .filter(|item| !removed.contains(item))
+                .map(|item| {
+                    VNode::Custom(VComponent::new::<Item>(ItemProps {
+                        name: item.clone(),
+                        on_remove: Callback::<Self, String>::new(BasketMessage::Remove),
+                    }))
+                    .key(item.clone())
+                })
+                .collect::<Vec<_>>();
+            nodes.push(VNode::Label(VLabel {
+                text: format!("{} items", items.len() - removed.len()),
+            }));
+            nodes
+        }
+        fn on_message(
+            message: &BasketMessage,
+            _items: &Self::Props,
+            removed: &mut Self::State,
+        ) -> bool {
+            let BasketMessage::Remove(item) = message;
+            removed.push(item.clone());
+            true
+        }
+    }
+
+    #[test]
+    fn callbacks_update_the_component_that_made_them() {
+        let harness = TestHarness::<Basket>::new(vec!["apples".to_string(), "pears".to_string()]);
+        let pears = harness.find_button("Remove pears").id();
+        HeadlessBackend::take_events();
+        harness.click(&harness.find_button("Remove apples"));
+        harness.find_label_by_text("1 items");
+        assert_eq!(harness.find_all(WidgetKind::Button).len(), 1);
+        // The callback given to the other item is equal to the one it had so it isn't updated
+        let updated = HeadlessBackend::take_events()
+            .into_iter()
+            .filter(|event| match event {
+                HeadlessEvent::Layout { children, .. } => children.contains(&pears),
+                _ => false,
+            })
+            .count();
+        assert_eq!(updated, 0);
+    }
+}
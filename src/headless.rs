@@ -0,0 +1,664 @@
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    fire_timers, Backend, Component, ContainerKind, ListRows, Message, NativeMenu, Renderable,
+    VComponent, VList, WindowAttributes,
+};
+
+thread_local! {
+    static NEXT_WIDGET_ID: RefCell<usize> = const { RefCell::new(0) };
+    static EVENTS: RefCell<Vec<HeadlessEvent>> = const { RefCell::new(Vec::new()) };
+    static DISPATCHED: RefCell<VecDeque<Message>> = const { RefCell::new(VecDeque::new()) };
+    /// Windows that are open, in the order they were opened
+    static WINDOWS: RefCell<Vec<HeadlessNode>> = const { RefCell::new(Vec::new()) };
+    static MENU_BAR: RefCell<Vec<NativeMenu>> = const { RefCell::new(Vec::new()) };
+    /// The widget with keyboard focus
+    static FOCUSED: Cell<Option<usize>> = const { Cell::new(None) };
+    /// The fake clock timers are measured against, it only moves when `advance_clock` is called
+    static NOW: Cell<Instant> = Cell::new(Instant::now());
+}
+
+/// Renders components into an in-memory widget tree so they can be run and inspected without AppKit.
+/// Everything the framework does to a widget is recorded as a `HeadlessEvent`, and messages sent by widgets are queued up rather than delivered.
+/// The log and the queue are per thread so tests running in parallel don't see each others widgets
+pub struct HeadlessBackend;
+
+impl HeadlessBackend {
+    /// Gives a component a fresh container and renders it into it, returning the container
+    pub fn mount(component: &dyn Renderable) -> HeadlessNode {
+        let container = Self::create_container();
+        component.set_parent_view(Box::new(container.clone()));
+        component.render();
+        container
+    }
+
+    /// Removes and returns everything recorded on this thread so far
+    pub fn take_events() -> Vec<HeadlessEvent> {
+        EVENTS.with(|events| events.take())
+    }
+
+    /// Moves this threads clock forward and fires every timer that has become due in the order they are due
+    pub fn advance_clock(by: Duration) {
+        NOW.with(|now| now.set(now.get() + by));
+        fire_timers::<Self>()
+    }
+
+    /// The windows open on this thread, oldest first
+    pub fn windows() -> Vec<HeadlessNode> {
+        WINDOWS.with(|windows| windows.borrow().clone())
+    }
+
+    /// The menus in this threads menu bar
+    pub fn menu_bar() -> Vec<NativeMenu> {
+        MENU_BAR.with(|bar| bar.borrow().clone())
+    }
+
+    /// The widget with keyboard focus on this thread, if any
+    pub fn focused() -> Option<usize> {
+        FOCUSED.with(Cell::get)
+    }
+
+    /// Moves focus without going through the backend, like a user tabbing to a widget
+    pub(crate) fn set_focus_by_user(node: &HeadlessNode) {
+        FOCUSED.with(|focused| focused.set(Some(node.id())))
+    }
+
+    /// Like AppKit, a window loses its first responder when the view that is or holds it is taken out of its superview
+    fn lose_focus_in(node: &HeadlessNode) {
+        if Self::has_focus(node) {
+            FOCUSED.with(|focused| focused.set(None))
+        }
+    }
+
+    /// Takes a window off the list of open ones, `TestHarness` also uses this when closing one like a user would
+    pub(crate) fn forget_window(window: &HeadlessNode) {
+        let id = window.id();
+        WINDOWS.with(|windows| windows.borrow_mut().retain(|window| window.id() != id));
+    }
+
+    /// Removes and returns the messages widgets have dispatched on this thread, oldest first
+    pub fn take_dispatched() -> Vec<Message> {
+        DISPATCHED.with(|dispatched| dispatched.take().into())
+    }
+
+    /// Rows are rendered eagerly here where AppKit would ask for them as they scroll into view
+    fn render_rows<T>(list_view: &HeadlessNode, list: &VList<T>, rows: &ListRows<T, Self>)
+    where
+        T: Component + Clone + PartialEq + 'static,
+    {
+        for index in 0..list.count {
+            let row = Self::create_container();
+            let cells = rows.create_row(list.render, index);
+            for cell in &cells {
+                Self::insert_child(&row, cell, None);
+            }
+            Self::apply_layout(&row, &cells.iter().collect::<Vec<_>>());
+            Self::insert_child(list_view, &row, None);
+        }
+    }
+
+    fn record(event: HeadlessEvent) {
+        EVENTS.with(|events| events.borrow_mut().push(event))
+    }
+
+    fn create_widget(kind: WidgetKind, text: &str) -> HeadlessNode {
+        let id = NEXT_WIDGET_ID.with(|next| {
+            let mut next = next.borrow_mut();
+            *next += 1;
+            *next
+        });
+        Self::record(HeadlessEvent::Create { widget: id, kind });
+        HeadlessNode(Rc::new(RefCell::new(HeadlessWidget {
+            id,
+            kind,
+            text: text.to_owned(),
+            options: Vec::new(),
+            selected: None,
+            handler: None,
+            key_handler: None,
+            window: None,
+            context_menu: None,
+            children: Vec::new(),
+        })))
+    }
+}
+
+impl Backend for HeadlessBackend {
+    type Node = HeadlessNode;
+    type Container = HeadlessNode;
+    type Window = HeadlessNode;
+
+    fn create_container() -> Self::Container {
+        Self::create_widget(WidgetKind::Container, "")
+    }
+
+    fn create_label(text: &str) -> Self::Node {
+        Self::create_widget(WidgetKind::Label, text)
+    }
+
+    fn create_button(text: &str) -> Self::Node {
+        Self::create_widget(WidgetKind::Button, text)
+    }
+
+    fn create_text_input(initial_value: &str) -> Self::Node {
+        Self::create_widget(WidgetKind::TextInput, initial_value)
+    }
+
+    fn create_select(options: &[String]) -> Self::Node {
+        let select = Self::create_widget(WidgetKind::Select, "");
+        select.0.borrow_mut().options = options.to_vec();
+        select
+    }
+
+    fn create_list<T>(list: &VList<T>, rows: ListRows<T, Self>) -> Self::Node
+    where
+        T: Component + Clone + PartialEq + 'static,
+    {
+        let list_view = Self::create_widget(WidgetKind::List, "");
+        Self::render_rows(&list_view, list, &rows);
+        list_view
+    }
+
+    fn create_custom(component: &VComponent) -> Self::Node {
+        let view = Self::create_widget(WidgetKind::Custom, "");
+        let renderable = component.renderable();
+        renderable.set_parent_view(Box::new(view.clone()));
+        renderable.render();
+        view
+    }
+
+    fn create_group(kind: &ContainerKind) -> Self::Node {
+        match kind {
+            ContainerKind::Stack => Self::create_widget(WidgetKind::Stack, ""),
+            ContainerKind::Group => Self::create_widget(WidgetKind::Group, ""),
+            ContainerKind::Box(title) => Self::create_widget(WidgetKind::Box, title),
+        }
+    }
+
+    fn as_container(node: &Self::Node) -> Option<&Self::Container> {
+        matches!(
+            node.kind(),
+            WidgetKind::Stack | WidgetKind::Group | WidgetKind::Box
+        )
+        .then_some(node)
+    }
+
+    fn set_text(node: &mut Self::Node, text: &str) {
+        let mut widget = node.0.borrow_mut();
+        Self::record(HeadlessEvent::SetText {
+            widget: widget.id,
+            text: text.to_owned(),
+        });
+        widget.text = text.to_owned();
+    }
+
+    fn set_options(node: &mut Self::Node, options: &[String]) {
+        let mut widget = node.0.borrow_mut();
+        Self::record(HeadlessEvent::SetOptions {
+            widget: widget.id,
+            options: options.to_vec(),
+        });
+        widget.options = options.to_vec();
+        if widget.selected.is_some_and(|index| index >= options.len()) {
+            widget.selected = None;
+        }
+    }
+
+    fn set_selected(node: &mut Self::Node, index: Option<usize>) {
+        let mut widget = node.0.borrow_mut();
+        Self::record(HeadlessEvent::SetSelected {
+            widget: widget.id,
+            index,
+        });
+        widget.selected = index;
+    }
+
+    fn update_list<T>(node: &mut Self::Node, list: &VList<T>, rows: &ListRows<T, Self>)
+    where
+        T: Component + Clone + PartialEq + 'static,
+    {
+        for row in node.children() {
+            Self::remove_child(node, &row);
+        }
+        Self::render_rows(node, list, rows);
+    }
+
+    fn set_handler(node: &mut Self::Node, id: Option<usize>) {
+        let mut widget = node.0.borrow_mut();
+        Self::record(HeadlessEvent::SetHandler {
+            widget: widget.id,
+            handler: id,
+        });
+        widget.handler = id;
+    }
+
+    fn set_context_menu(node: &mut Self::Node, menu: Option<&NativeMenu>) {
+        let mut widget = node.0.borrow_mut();
+        Self::record(HeadlessEvent::SetContextMenu {
+            widget: widget.id,
+            menu: menu.cloned(),
+        });
+        widget.context_menu = menu.cloned();
+    }
+
+    fn set_key_handler(node: &mut Self::Node, id: Option<usize>) {
+        let mut widget = node.0.borrow_mut();
+        Self::record(HeadlessEvent::SetKeyHandler {
+            widget: widget.id,
+            handler: id,
+        });
+        widget.key_handler = id;
+    }
+
+    fn set_container_key_handler(container: &Self::Container, id: usize) {
+        let mut widget = container.0.borrow_mut();
+        Self::record(HeadlessEvent::SetKeyHandler {
+            widget: widget.id,
+            handler: Some(id),
+        });
+        widget.key_handler = Some(id);
+    }
+
+    fn set_menu_bar(menus: &[NativeMenu]) {
+        Self::record(HeadlessEvent::SetMenuBar {
+            menus: menus.to_vec(),
+        });
+        MENU_BAR.with(|bar| *bar.borrow_mut() = menus.to_vec());
+    }
+
+    fn insert_child(parent: &Self::Container, child: &Self::Node, before: Option<&Self::Node>) {
+        Self::record(HeadlessEvent::InsertChild {
+            parent: parent.id(),
+            child: child.id(),
+            before: before.map(HeadlessNode::id),
+        });
+        parent.place_child(child, before);
+    }
+
+    fn move_child(parent: &Self::Container, child: &Self::Node, before: Option<&Self::Node>) {
+        let id = child.id();
+        Self::record(HeadlessEvent::MoveChild {
+            parent: parent.id(),
+            child: id,
+            before: before.map(HeadlessNode::id),
+        });
+        Self::lose_focus_in(child);
+        parent
+            .0
+            .borrow_mut()
+            .children
+            .retain(|node| node.id() != id);
+        parent.place_child(child, before);
+    }
+
+    fn remove_child(parent: &Self::Container, child: &Self::Node) {
+        Self::lose_focus_in(child);
+        let child = child.id();
+        Self::record(HeadlessEvent::RemoveChild {
+            parent: parent.id(),
+            child,
+        });
+        parent
+            .0
+            .borrow_mut()
+            .children
+            .retain(|node| node.id() != child);
+    }
+
+    fn apply_layout(parent: &Self::Container, children: &[&Self::Node]) {
+        let order = children.iter().map(|child| child.id()).collect::<Vec<_>>();
+        // Inserts and moves are meant to have put the children in the order they are laid out in already
+        debug_assert_eq!(
+            parent
+                .children()
+                .iter()
+                .map(HeadlessNode::id)
+                .collect::<Vec<_>>(),
+            order,
+            "Children are out of order"
+        );
+        Self::record(HeadlessEvent::Layout {
+            parent: parent.id(),
+            children: order,
+        });
+    }
+
+    fn open_window(
+        attributes: &WindowAttributes,
+        content: &VComponent,
+        close: usize,
+    ) -> Self::Window {
+        let window = Self::create_widget(WidgetKind::Window, &attributes.title);
+        Self::record(HeadlessEvent::OpenWindow {
+            window: window.id(),
+            attributes: attributes.clone(),
+        });
+        {
+            let mut widget = window.0.borrow_mut();
+            widget.handler = Some(close);
+            widget.window = Some(attributes.clone());
+        }
+        WINDOWS.with(|windows| windows.borrow_mut().push(window.clone()));
+        let view = Self::create_custom(content);
+        Self::insert_child(&window, &view, None);
+        window
+    }
+
+    fn update_window(window: &mut Self::Window, _old: &WindowAttributes, new: &WindowAttributes) {
+        let mut widget = window.0.borrow_mut();
+        Self::record(HeadlessEvent::UpdateWindow {
+            window: widget.id,
+            attributes: new.clone(),
+        });
+        widget.text = new.title.clone();
+        widget.window = Some(new.clone());
+    }
+
+    fn close_window(window: Self::Window) {
+        Self::record(HeadlessEvent::CloseWindow {
+            window: window.id(),
+        });
+        Self::forget_window(&window);
+    }
+
+    fn dispatch(message: Message) {
+        DISPATCHED.with(|dispatched| dispatched.borrow_mut().push_back(message))
+    }
+
+    /// There is no run loop here, tests call `flush` themselves or let `TestHarness` do it
+    fn schedule_flush() {}
+
+    /// Tasks are polled by `poll_tasks` when tests call it or `TestHarness` does
+    fn schedule_poll() {}
+
+    fn now() -> Instant {
+        NOW.with(Cell::get)
+    }
+
+    /// Timers fire when tests call `advance_clock`
+    fn schedule_timer(_delay: Duration) {}
+
+    fn announce(text: &str) {
+        Self::record(HeadlessEvent::Announce {
+            text: text.to_string(),
+        })
+    }
+
+    fn focus(node: &Self::Node) {
+        Self::record(HeadlessEvent::Focus { widget: node.id() });
+        FOCUSED.with(|focused| focused.set(Some(node.id())))
+    }
+
+    fn focus_container(container: &Self::Container) {
+        Self::focus(container)
+    }
+
+    fn has_focus(node: &Self::Node) -> bool {
+        Self::focused()
+            .is_some_and(|focused| node.descendants().iter().any(|node| node.id() == focused))
+    }
+
+    fn quit() {
+        Self::record(HeadlessEvent::Quit)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WidgetKind {
+    Container,
+    Label,
+    Button,
+    TextInput,
+    Select,
+    List,
+    Custom,
+    Stack,
+    Group,
+    /// A group with its title as its text
+    Box,
+    /// A top level window with its title as its text, its handler is where its close message goes
+    Window,
+}
+
+pub struct HeadlessWidget {
+    /// Unique to the thread the widget was created on
+    pub id: usize,
+    pub kind: WidgetKind,
+    /// The text of labels and buttons, the value of text inputs or the title of boxes
+    pub text: String,
+    pub options: Vec<String>,
+    /// The index of the chosen option of a select
+    pub selected: Option<usize>,
+    /// The id of the handler this widget dispatches messages to
+    pub handler: Option<usize>,
+    /// The id key presses while the widget or one of its children has focus are dispatched with
+    pub key_handler: Option<usize>,
+    /// The attributes of a window
+    pub window: Option<WindowAttributes>,
+    pub context_menu: Option<NativeMenu>,
+    pub children: Vec<HeadlessNode>,
+}
+
+/// A shared handle to a widget in the headless tree
+#[derive(Clone)]
+pub struct HeadlessNode(Rc<RefCell<HeadlessWidget>>);
+
+impl HeadlessNode {
+    pub fn borrow(&self) -> Ref<'_, HeadlessWidget> {
+        self.0.borrow()
+    }
+
+    pub fn id(&self) -> usize {
+        self.0.borrow().id
+    }
+
+    pub fn kind(&self) -> WidgetKind {
+        self.0.borrow().kind
+    }
+
+    pub fn text(&self) -> String {
+        self.0.borrow().text.clone()
+    }
+
+    pub fn handler(&self) -> Option<usize> {
+        self.0.borrow().handler
+    }
+
+    pub fn children(&self) -> Vec<HeadlessNode> {
+        self.0.borrow().children.clone()
+    }
+
+    /// Changes the value without going through the backend, like a user typing into a text field
+    pub(crate) fn set_value(&self, text: &str) {
+        self.0.borrow_mut().text = text.to_owned();
+    }
+
+    /// Chooses an option without going through the backend, like a user picking from a select
+    pub(crate) fn set_selected_by_user(&self, index: usize) {
+        self.0.borrow_mut().selected = Some(index);
+    }
+
+    fn place_child(&self, child: &HeadlessNode, before: Option<&HeadlessNode>) {
+        let mut widget = self.0.borrow_mut();
+        let position = before
+            .and_then(|before| {
+                let before = before.id();
+                widget.children.iter().position(|node| node.id() == before)
+            })
+            .unwrap_or(widget.children.len());
+        widget.children.insert(position, child.clone());
+    }
+
+    /// This node followed by all of its descendants, depth first
+    pub fn descendants(&self) -> Vec<HeadlessNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.0.borrow().children.iter() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeadlessEvent {
+    Create {
+        widget: usize,
+        kind: WidgetKind,
+    },
+    SetText {
+        widget: usize,
+        text: String,
+    },
+    SetOptions {
+        widget: usize,
+        options: Vec<String>,
+    },
+    SetSelected {
+        widget: usize,
+        index: Option<usize>,
+    },
+    SetHandler {
+        widget: usize,
+        handler: Option<usize>,
+    },
+    InsertChild {
+        parent: usize,
+        child: usize,
+        before: Option<usize>,
+    },
+    MoveChild {
+        parent: usize,
+        child: usize,
+        before: Option<usize>,
+    },
+    RemoveChild {
+        parent: usize,
+        child: usize,
+    },
+    Layout {
+        parent: usize,
+        children: Vec<usize>,
+    },
+    Announce {
+        text: String,
+    },
+    Focus {
+        widget: usize,
+    },
+    Quit,
+    OpenWindow {
+        window: usize,
+        attributes: WindowAttributes,
+    },
+    UpdateWindow {
+        window: usize,
+        attributes: WindowAttributes,
+    },
+    CloseWindow {
+        window: usize,
+    },
+    SetContextMenu {
+        widget: usize,
+        menu: Option<NativeMenu>,
+    },
+    SetKeyHandler {
+        widget: usize,
+        handler: Option<usize>,
+    },
+    SetMenuBar {
+        menus: Vec<NativeMenu>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flush, ClickHandler, ComponentWrapper, VButton, VLabel, VNode};
+
+    #[derive(PartialEq, Clone)]
+    struct Greeting;
+
+    impl Component for Greeting {
+        type Props = String;
+        type State = ();
+        fn render(props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::Label(VLabel {
+                text: format!("Hello {props}"),
+            })]
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Page;
+
+    impl Component for Page {
+        type Props = String;
+        type State = ();
+        fn render(props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Custom(VComponent::new::<Greeting>(props.clone())),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, _| {})),
+                    text: "Done".to_string(),
+                }),
+            ]
+        }
+    }
+
+    #[test]
+    fn renders_nested_components() {
+        let page = ComponentWrapper::<Page, HeadlessBackend>::new("world".to_string());
+        let root = HeadlessBackend::mount(&page);
+        let kinds = root
+            .descendants()
+            .iter()
+            .map(|node| node.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                WidgetKind::Container,
+                WidgetKind::Custom,
+                WidgetKind::Label,
+                WidgetKind::Button
+            ]
+        );
+        assert_eq!(root.children()[0].children()[0].text(), "Hello world");
+        assert!(root.children()[1].handler().is_some());
+    }
+
+    #[test]
+    fn records_updates_to_existing_widgets() {
+        let page = ComponentWrapper::<Page, HeadlessBackend>::new("world".to_string());
+        let root = HeadlessBackend::mount(&page);
+        let greeting = root.children()[0].id();
+        let label = root.children()[0].children()[0].id();
+        HeadlessBackend::take_events();
+        page.update_props("there".to_string());
+        assert_eq!(HeadlessBackend::take_events(), []);
+        flush();
+        // The page renders first and the greeting only once it has its new props
+        assert_eq!(
+            HeadlessBackend::take_events(),
+            [
+                HeadlessEvent::Layout {
+                    parent: root.id(),
+                    children: root.children().iter().map(HeadlessNode::id).collect()
+                },
+                HeadlessEvent::SetText {
+                    widget: label,
+                    text: "Hello there".to_string()
+                },
+                HeadlessEvent::Layout {
+                    parent: greeting,
+                    children: vec![label]
+                },
+            ]
+        );
+    }
+}
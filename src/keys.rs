@@ -0,0 +1,311 @@
+/// A key pressed while a widget had keyboard focus
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyPress {
+    /// The hardware code of the key, the same whatever the keyboard layout
+    pub key_code: u16,
+    /// The text the key types with the modifiers held
+    pub characters: String,
+    /// The text the key types with only shift applied, if it is held, as control and option change the characters
+    pub characters_ignoring_modifiers: String,
+    /// The text the key types without any modifiers, not even shift
+    pub unshifted_characters: String,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Modifiers {
+    pub command: bool,
+    pub shift: bool,
+    pub option: bool,
+    pub control: bool,
+}
+
+/// A key and the modifiers held with it, which `Component::shortcuts` maps to messages
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Chord {
+    /// What the key types, either without any modifiers like `"k"` and `"/"` or with shift like `"K"` and `"?"`.
+    /// Without modifiers shift is compared like the other modifiers, so `Chord::new("/").command().shift()` is command shift slash.
+    /// With shift it is already implied, so `Chord::new("?").command()` is the same chord on a US keyboard
+    pub key: String,
+    pub modifiers: Modifiers,
+}
+
+impl Chord {
+    /// The key without any modifiers
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    pub fn command(mut self) -> Self {
+        self.modifiers.command = true;
+        self
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.modifiers.shift = true;
+        self
+    }
+
+    pub fn option(mut self) -> Self {
+        self.modifiers.option = true;
+        self
+    }
+
+    pub fn control(mut self) -> Self {
+        self.modifiers.control = true;
+        self
+    }
+
+    /// Whether the press is of this chord, see `Chord::key` for how the key is compared
+    pub fn matches(&self, press: &KeyPress) -> bool {
+        let (chord, held) = (self.modifiers, press.modifiers);
+        if (chord.command, chord.option, chord.control) != (held.command, held.option, held.control)
+        {
+            return false;
+        }
+        if self.key == press.unshifted_characters {
+            return chord.shift == held.shift;
+        }
+        held.shift && self.key == press.characters_ignoring_modifiers
+    }
+
+    /// A press of the chord as a US keyboard sends it, for tests to send.
+    /// Keys that aren't on one are given the key code `u16::MAX` and type themselves whether or not shift is held
+    pub fn press(&self) -> KeyPress {
+        let (key_code, unshifted, shifted) = US_KEYS
+            .iter()
+            .find(|(_, unshifted, shifted)| *unshifted == self.key || *shifted == self.key)
+            .copied()
+            .unwrap_or((u16::MAX, self.key.as_str(), self.key.as_str()));
+        let mut modifiers = self.modifiers;
+        modifiers.shift |= self.key == shifted && shifted != unshifted;
+        let ignoring_modifiers = if modifiers.shift { shifted } else { unshifted };
+        // Control turns letters into the control characters, option types characters that depend on the layout and is left out
+        let characters = match ignoring_modifiers.as_bytes() {
+            [letter] if modifiers.control && letter.is_ascii_alphabetic() => {
+                char::from(letter & 0x1f).to_string()
+            }
+            _ => ignoring_modifiers.to_string(),
+        };
+        KeyPress {
+            key_code,
+            characters,
+            characters_ignoring_modifiers: ignoring_modifiers.to_string(),
+            unshifted_characters: unshifted.to_string(),
+            modifiers,
+        }
+    }
+}
+
+/// The key code of each key on a US keyboard with what it types without and with shift
+const US_KEYS: [(u16, &str, &str); 52] = [
+    (0x00, "a", "A"),
+    (0x01, "s", "S"),
+    (0x02, "d", "D"),
+    (0x03, "f", "F"),
+    (0x04, "h", "H"),
+    (0x05, "g", "G"),
+    (0x06, "z", "Z"),
+    (0x07, "x", "X"),
+    (0x08, "c", "C"),
+    (0x09, "v", "V"),
+    (0x0B, "b", "B"),
+    (0x0C, "q", "Q"),
+    (0x0D, "w", "W"),
+    (0x0E, "e", "E"),
+    (0x0F, "r", "R"),
+    (0x10, "y", "Y"),
+    (0x11, "t", "T"),
+    (0x12, "1", "!"),
+    (0x13, "2", "@"),
+    (0x14, "3", "#"),
+    (0x15, "4", "$"),
+    (0x16, "6", "^"),
+    (0x17, "5", "%"),
+    (0x18, "=", "+"),
+    (0x19, "9", "("),
+    (0x1A, "7", "&"),
+    (0x1B, "-", "_"),
+    (0x1C, "8", "*"),
+    (0x1D, "0", ")"),
+    (0x1E, "]", "}"),
+    (0x1F, "o", "O"),
+    (0x20, "u", "U"),
+    (0x21, "[", "{"),
+    (0x22, "i", "I"),
+    (0x23, "p", "P"),
+    (0x24, "\r", "\r"),
+    (0x25, "l", "L"),
+    (0x26, "j", "J"),
+    (0x27, "'", "\""),
+    (0x28, "k", "K"),
+    (0x29, ";", ":"),
+    (0x2A, "\\", "|"),
+    (0x2B, ",", "<"),
+    (0x2C, "/", "?"),
+    (0x2D, "n", "N"),
+    (0x2E, "m", "M"),
+    (0x2F, ".", ">"),
+    (0x30, "\t", "\t"),
+    (0x31, " ", " "),
+    (0x32, "`", "~"),
+    (0x33, "\u{7f}", "\u{7f}"),
+    (0x35, "\u{1b}", "\u{1b}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ChangeHandler, Chord, Component, KeyDownHandler, KeyPress, TestHarness, VComponent, VLabel,
+        VNode, VTextInput,
+    };
+
+    #[derive(PartialEq, Clone)]
+    struct Launcher;
+
+    #[derive(PartialEq, Clone)]
+    enum LauncherMessage {
+        Toggle,
+    }
+
+    impl Component for Launcher {
+        type Props = ();
+        type State = bool;
+        type Message = LauncherMessage;
+        fn render(_props: &Self::Props, open: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Label(VLabel {
+                    text: if *open { "Open" } else { "Closed" }.to_string(),
+                }),
+                VNode::Custom(VComponent::new::<Search>(())),
+            ]
+        }
+        fn on_message(
+            _msg: &Self::Message,
+            _props: &Self::Props,
+            open: &mut Self::State,
+        ) -> Self::Update {
+            *open = !*open;
+            true
+        }
+        fn shortcuts(_props: &Self::Props, _state: &Self::State) -> Vec<(Chord, Self::Message)> {
+            vec![
+                (Chord::new("k").command(), LauncherMessage::Toggle),
+                (Chord::new("l").control(), LauncherMessage::Toggle),
+            ]
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Search;
+
+    #[derive(PartialEq, Clone, Default)]
+    struct SearchState {
+        query: String,
+        searches: usize,
+    }
+
+    impl Component for Search {
+        type Props = ();
+        type State = SearchState;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::TextInput(VTextInput {
+                    change: Some(ChangeHandler::<Self>::new(|value, _, state| {
+                        state.query = value.to_string();
+                        true
+                    })),
+                    initial_value: state.query.clone(),
+                })
+                .on_key_down(KeyDownHandler::<Self>::new(|press, _, state| {
+                    // Escape clears the query, everything else is left to the shortcuts
+                    (press.characters == "\u{1b}").then(|| {
+                        state.query.clear();
+                        true
+                    })
+                })),
+                VNode::Label(VLabel {
+                    text: format!("Searches {}", state.searches),
+                }),
+            ]
+        }
+        fn on_message(
+            _msg: &Self::Message,
+            _props: &Self::Props,
+            state: &mut Self::State,
+        ) -> Self::Update {
+            state.searches += 1;
+            true
+        }
+        fn shortcuts(_props: &Self::Props, _state: &Self::State) -> Vec<(Chord, Self::Message)> {
+            vec![(Chord::new("f").command(), ())]
+        }
+    }
+
+    #[test]
+    fn routes_key_presses_from_the_focused_node_up_to_its_ancestors() {
+        let harness = TestHarness::<Launcher>::new(());
+        let input = harness.find_text_input("");
+        harness.type_into(&input, "cat");
+        assert!(harness.press_key(&input, Chord::new("\u{1b}").press()));
+        harness.find_text_input("");
+        // The key handler passes this on to the shortcuts of its component
+        assert!(harness.press_key(&input, Chord::new("f").command().press()));
+        harness.find_label_by_text("Searches 1");
+        // Neither the handler nor the search shortcuts take this so it bubbles up to the launcher
+        assert!(harness.press_key(&input, Chord::new("k").command().press()));
+        harness.find_label_by_text("Open");
+        assert!(!harness.press_key(&input, Chord::new("x").press()));
+        // Widgets without a handler of their own go to the component they are in
+        let label = harness.find_label_by_text("Open");
+        assert!(harness.press_key(&label, Chord::new("k").command().press()));
+        harness.find_label_by_text("Closed");
+        assert!(!harness.press_key(&label, Chord::new("f").command().press()));
+    }
+
+    #[test]
+    fn matches_chords_by_the_characters_without_modifiers() {
+        let harness = TestHarness::<Launcher>::new(());
+        let label = harness.find_label_by_text("Closed");
+        // Control-L types a form feed
+        let press = KeyPress {
+            key_code: 37,
+            characters: "\u{c}".to_string(),
+            characters_ignoring_modifiers: "l".to_string(),
+            unshifted_characters: "l".to_string(),
+            modifiers: Chord::new("l").control().modifiers,
+        };
+        assert!(harness.press_key(&label, press));
+        harness.find_label_by_text("Open");
+    }
+
+    #[test]
+    fn matches_shifted_keys_by_either_character() {
+        // Command shift slash as a US keyboard sends it
+        let press = KeyPress {
+            key_code: 0x2C,
+            characters: "?".to_string(),
+            characters_ignoring_modifiers: "?".to_string(),
+            unshifted_characters: "/".to_string(),
+            modifiers: Chord::new("/").command().shift().modifiers,
+        };
+        assert!(Chord::new("/").command().shift().matches(&press));
+        assert!(Chord::new("?").command().matches(&press));
+        assert!(Chord::new("?").command().shift().matches(&press));
+        assert!(!Chord::new("/").command().matches(&press));
+        assert!(!Chord::new("?").matches(&press));
+        assert_eq!(Chord::new("?").command().press(), press);
+        assert_eq!(Chord::new("/").command().shift().press(), press);
+
+        let press = Chord::new("K").command().press();
+        assert_eq!(press.unshifted_characters, "k");
+        assert!(Chord::new("k").command().shift().matches(&press));
+        assert!(!Chord::new("k").command().matches(&press));
+        assert!(!Chord::new("K")
+            .command()
+            .matches(&Chord::new("k").command().press()));
+    }
+}
@@ -1,9 +1,49 @@
 #![feature(array_windows)]
 #![feature(associated_type_defaults)]
+#[cfg(target_os = "macos")]
+mod app;
+mod backend;
+#[cfg(target_os = "macos")]
+mod cacao_backend;
+mod command;
 mod component;
+mod executor;
+mod handler;
+mod headless;
+mod keys;
+#[cfg(target_os = "macos")]
 mod layout;
+#[cfg(target_os = "macos")]
 mod list_view;
+mod menu;
+mod reconcile;
+mod routing;
+mod scheduler;
+mod snapshot;
+mod subscription;
+mod testing;
+mod timer;
+mod window;
+#[cfg(target_os = "macos")]
+pub use app::run;
+pub use backend::*;
+#[cfg(target_os = "macos")]
+pub use cacao_backend::*;
+pub use command::*;
 pub use component::*;
+pub use executor::poll_tasks;
+pub use handler::*;
+pub use headless::*;
+pub use keys::*;
+pub use menu::{NativeMenu, NativeMenuItem, VMenu, VMenuItem};
+pub use reconcile::*;
+pub use routing::*;
+pub use scheduler::flush;
+pub use snapshot::*;
+pub use subscription::*;
+pub use testing::*;
+pub use timer::fire_timers;
+pub use window::*;
 
 #[cfg(test)]
 mod tests {
@@ -16,22 +56,25 @@ mod tests {
     impl Component for CustomComponent {
         type Props = ();
         type State = u32;
-        fn render(_props: &Self::Props, state: &Self::State) -> Vec<(usize, VNode<Self>)> {
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
             vec![
-                (
-                    0,
-                    VNode::Button(VButton {
-                        click: Some(|_, state| *state += 1),
-                        text: "Increment".to_string(),
-                    }),
-                ),
-                (
-                    0,
-                    VNode::Label(VLabel {
-                        text: state.to_string(),
-                    }),
-                ),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, state| *state += 1)),
+                    text: "Increment".to_string(),
+                }),
+                VNode::Label(VLabel {
+                    text: state.to_string(),
+                }),
             ]
         }
     }
+
+    #[test]
+    fn clicking_increment_updates_the_count() {
+        let harness = TestHarness::<CustomComponent>::new(());
+        harness.find_label_by_text("0");
+        harness.click(&harness.find_button("Increment"));
+        harness.find_label_by_text("1");
+        assert_eq!(harness.find_all(WidgetKind::Label).len(), 1);
+    }
 }
@@ -1,64 +1,75 @@
-use std::cell::RefCell;
+use std::any::Any;
 use std::marker::PhantomData;
-use std::rc::Rc;
 
-use cacao::appkit::AppDelegate;
 use cacao::layout::{Layout, LayoutConstraint};
 use cacao::listview::{ListView, ListViewDelegate};
-use cacao::notification_center::Dispatcher;
 use cacao::view::{View, ViewDelegate};
 
 use crate::layout::top_to_bottom;
-use crate::{CacaoComponent, Component, ComponentWrapper, Message, VNode};
+use crate::{CacaoBackend, CacaoComponent, Component, ListRender, ListRows};
 
 /// A generic list view
-pub struct MyListView<T: Component, D: Dispatcher<Message> + AppDelegate> {
+pub struct MyListView<T: Component + PartialEq> {
     view: Option<ListView>,
     count: usize,
-    render: fn(usize, &T::Props, &T::State) -> Vec<VNode<T>>,
-    props: Rc<RefCell<T::Props>>,
-    state: Rc<RefCell<T::State>>,
-    app: PhantomData<D>,
-    component: PhantomData<T>,
+    render: ListRender<T>,
+    rows: ListRows<T, CacaoBackend>,
 }
 
-impl<T, D> MyListView<T, D>
+impl<T> MyListView<T>
 where
     T: Component + Clone + PartialEq + 'static,
-    D: Dispatcher<Message> + AppDelegate + 'static,
 {
-    pub fn new(
-        count: usize,
-        render: fn(usize, &T::Props, &T::State) -> Vec<VNode<T>>,
-        props: Rc<RefCell<T::Props>>,
-        state: Rc<RefCell<T::State>>,
-    ) -> Self {
+    pub fn new(count: usize, render: ListRender<T>, rows: ListRows<T, CacaoBackend>) -> Self {
         Self {
             view: None,
             count,
             render,
-            props,
-            state,
-            app: PhantomData,
-            component: PhantomData,
+            rows,
+        }
+    }
+
+    /// Points the list at a new count or render function and reloads the rows
+    pub fn update(&mut self, count: usize, render: ListRender<T>) {
+        self.count = count;
+        self.render = render;
+        if let Some(view) = &self.view {
+            view.reload();
         }
     }
 
     /// Not a good name
     pub fn with(
         count: usize,
-        render: fn(usize, &T::Props, &T::State) -> Vec<VNode<T>>,
-        props: Rc<RefCell<T::Props>>,
-        state: Rc<RefCell<T::State>>,
+        render: ListRender<T>,
+        rows: ListRows<T, CacaoBackend>,
     ) -> ListView<Self> {
-        ListView::with(Self::new(count, render, props, state))
+        ListView::with(Self::new(count, render, rows))
+    }
+}
+
+/// Lets a `ListView` be stored without naming the component its rows come from
+pub trait AnyListView {
+    fn as_layout(&self) -> &dyn Layout;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T> AnyListView for ListView<MyListView<T>>
+where
+    T: Component + Clone + PartialEq + 'static,
+{
+    fn as_layout(&self) -> &dyn Layout {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
 
-impl<T, D> ListViewDelegate for MyListView<T, D>
+impl<T> ListViewDelegate for MyListView<T>
 where
     T: Component + Clone + PartialEq + 'static,
-    D: Dispatcher<Message> + AppDelegate + 'static,
 {
     const NAME: &'static str = "ThisIsIgnored";
     fn subclass_name(&self) -> &'static str {
@@ -66,7 +77,7 @@ where
     }
     /// Essential configuration and retaining of a `ListView` handle to do updates later on.
     fn did_load(&mut self, view: ListView) {
-        view.register(std::any::type_name::<T>(), Row::<T, D>::new);
+        view.register(std::any::type_name::<T>(), Row::<T>::new);
         view.set_row_height(64.);
         LayoutConstraint::activate(&[
             view.height.constraint_equal_to_constant(100.0),
@@ -86,59 +97,40 @@ where
             .view
             .as_ref()
             .unwrap()
-            .dequeue::<Row<T, D>>(std::any::type_name::<T>());
+            .dequeue::<Row<T>>(std::any::type_name::<T>());
         if let Some(view) = &mut view.delegate {
-            view.as_mut().configure_with(
-                self.render,
-                row,
-                &*self.props.borrow(),
-                &*self.state.borrow(),
-            );
+            view.as_mut().configure_with(self.render, row, &self.rows);
         }
 
         view.into_row()
     }
 }
 
-pub struct Row<T: Component + Clone + PartialEq, D: Dispatcher<Message> + AppDelegate> {
+pub struct Row<T: Component + Clone + PartialEq> {
     view: View,
-    sub_views: Vec<CacaoComponent<T, D>>,
+    sub_views: Vec<CacaoComponent>,
     component: PhantomData<T>,
-    app: PhantomData<D>,
 }
 
-impl<
-        T: Component + Clone + PartialEq + 'static,
-        D: Dispatcher<Message> + AppDelegate + 'static,
-    > Row<T, D>
-{
+impl<T: Component + Clone + PartialEq + 'static> Row<T> {
     pub fn new() -> Self {
         Self {
             view: View::new(),
             sub_views: Vec::new(),
             component: PhantomData,
-            app: PhantomData,
         }
     }
 
     fn configure_with(
         &mut self,
-        render: fn(usize, &T::Props, &T::State) -> Vec<VNode<T>>,
+        render: ListRender<T>,
         index: usize,
-        props: &T::Props,
-        state: &T::State,
+        rows: &ListRows<T, CacaoBackend>,
     ) {
-        let mut vdom = render(index, props, state);
         for view in &self.sub_views {
             view.as_layout().remove_from_superview();
         }
-        // Sshhh bit of a hack but it works
-        // TODO: Try make it work better in the future
-        let comp = ComponentWrapper::<T, D>::new(props.clone());
-        self.sub_views = vdom
-            .iter_mut()
-            .map(|node| comp.create_component(node))
-            .collect();
+        self.sub_views = rows.create_row(render, index);
         for view in &self.sub_views {
             self.view.add_subview(view.as_layout())
         }
@@ -150,9 +142,7 @@ impl<
     }
 }
 
-impl<T: Component + Clone + PartialEq, D: Dispatcher<Message> + AppDelegate> ViewDelegate
-    for Row<T, D>
-{
+impl<T: Component + Clone + PartialEq> ViewDelegate for Row<T> {
     const NAME: &'static str = "Row";
     fn did_load(&mut self, view: View) {
         view.add_subview(&self.view);
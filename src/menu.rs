@@ -0,0 +1,238 @@
+use std::cell::RefCell;
+
+use crate::{Backend, ClickHandler, Component};
+
+/// A menu in the menu bar, returned from `Component::menu_bar`, or one opened from a node given it with `VNode::context_menu`.
+/// Items are matched up with those rendered in the same position last time, an item keeps its handler registered for as long as its handler compares equal
+pub struct VMenu<T: Component + ?Sized> {
+    /// Shown in the menu bar, or read out by VoiceOver for a context menu
+    pub title: String,
+    pub items: Vec<VMenuItem<T>>,
+}
+
+pub struct VMenuItem<T: Component + ?Sized> {
+    pub title: String,
+    /// The key that chooses the item when pressed with command, such as `"s"`
+    pub key_equivalent: Option<String>,
+    pub enabled: bool,
+    /// Shows a check mark next to the item
+    pub checked: bool,
+    pub on_select: Option<ClickHandler<T>>,
+}
+
+impl<T: Component + ?Sized> VMenu<T> {
+    pub fn new(title: impl Into<String>, items: Vec<VMenuItem<T>>) -> Self {
+        Self {
+            title: title.into(),
+            items,
+        }
+    }
+}
+
+impl<T: Component + ?Sized> VMenuItem<T> {
+    /// An enabled, unchecked item without a key equivalent
+    pub fn new(title: impl Into<String>, on_select: ClickHandler<T>) -> Self {
+        Self {
+            title: title.into(),
+            key_equivalent: None,
+            enabled: true,
+            checked: false,
+            on_select: Some(on_select),
+        }
+    }
+
+    pub fn key_equivalent(mut self, key: impl Into<String>) -> Self {
+        self.key_equivalent = Some(key.into());
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.checked = checked;
+        self
+    }
+}
+
+// Derived comparisons would need the component to be comparable as well
+impl<T: Component + ?Sized> PartialEq for VMenu<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.title == other.title && self.items == other.items
+    }
+}
+
+impl<T: Component + ?Sized> PartialEq for VMenuItem<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.title == other.title
+            && self.key_equivalent == other.key_equivalent
+            && self.enabled == other.enabled
+            && self.checked == other.checked
+            && self.on_select == other.on_select
+    }
+}
+
+/// A menu as the backend builds it, with the handlers of its items swapped for the ids they were registered under
+#[derive(Clone, PartialEq, Debug)]
+pub struct NativeMenu {
+    pub title: String,
+    pub items: Vec<NativeMenuItem>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct NativeMenuItem {
+    pub title: String,
+    pub key_equivalent: Option<String>,
+    pub enabled: bool,
+    pub checked: bool,
+    /// Choosing the item dispatches `Message::click` with this id, `None` when it has no handler
+    pub id: Option<usize>,
+}
+
+thread_local! {
+    /// The menus each component has added to the menu bar, in the order the components first added any
+    static MENU_BAR: RefCell<Vec<(usize, Vec<NativeMenu>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Replaces the menus `owner` has in the menu bar, handing the whole bar to the backend if that changed it
+pub(crate) fn set_menu_bar<B: Backend>(owner: usize, menus: Vec<NativeMenu>) {
+    let bar = MENU_BAR.with(|bar| {
+        let mut bar = bar.borrow_mut();
+        let position = bar.iter().position(|(other, _)| *other == owner);
+        match position {
+            Some(index) if bar[index].1 == menus => return None,
+            Some(index) if menus.is_empty() => {
+                bar.remove(index);
+            }
+            Some(index) => bar[index].1 = menus,
+            None if menus.is_empty() => return None,
+            None => bar.push((owner, menus)),
+        }
+        Some(
+            bar.iter()
+                .flat_map(|(_, menus)| menus.iter().cloned())
+                .collect::<Vec<_>>(),
+        )
+    });
+    if let Some(bar) = bar {
+        B::set_menu_bar(&bar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClickHandler, Component, HeadlessBackend, HeadlessEvent, HeadlessNode, TestHarness, VLabel,
+        VMenu, VMenuItem, VNode, WidgetKind,
+    };
+
+    #[derive(PartialEq, Clone)]
+    struct Notes;
+
+    #[derive(PartialEq, Clone, Default)]
+    struct NotesState {
+        notes: Vec<String>,
+        wrap: bool,
+    }
+
+    impl Component for Notes {
+        type Props = ();
+        type State = NotesState;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            state
+                .notes
+                .iter()
+                .enumerate()
+                .map(|(index, note)| {
+                    VNode::Label(VLabel { text: note.clone() })
+                        .key(note.clone())
+                        .context_menu(VMenu::new(
+                            note.clone(),
+                            vec![VMenuItem::new(
+                                "Delete",
+                                ClickHandler::<Self>::new(move |_, state: &mut NotesState| {
+                                    state.notes.remove(index);
+                                })
+                                .deps(index),
+                            )],
+                        ))
+                })
+                .collect()
+        }
+        fn menu_bar(_props: &Self::Props, state: &Self::State) -> Vec<VMenu<Self>> {
+            vec![VMenu::new(
+                "File",
+                vec![
+                    VMenuItem::new(
+                        "New",
+                        ClickHandler::<Self>::new(|_, state: &mut NotesState| {
+                            let note = format!("Note {}", state.notes.len() + 1);
+                            state.notes.push(note)
+                        }),
+                    )
+                    .key_equivalent("n"),
+                    VMenuItem::new(
+                        "Wrap lines",
+                        ClickHandler::<Self>::new(|_, state: &mut NotesState| {
+                            state.wrap = !state.wrap
+                        }),
+                    )
+                    .checked(state.wrap)
+                    .enabled(!state.notes.is_empty()),
+                ],
+            )]
+        }
+    }
+
+    #[test]
+    fn keeps_the_menu_bar_up_to_date() {
+        let harness = TestHarness::<Notes>::new(());
+        let bar = HeadlessBackend::menu_bar();
+        assert_eq!(bar.len(), 1);
+        let new = bar[0].items[0].clone();
+        assert_eq!(new.key_equivalent.as_deref(), Some("n"));
+        assert!(!bar[0].items[1].enabled);
+        harness.choose_menu_item("File", "New");
+        harness.find_label_by_text("Note 1");
+        harness.choose_menu_item("File", "Wrap lines");
+        let bar = HeadlessBackend::menu_bar();
+        assert!(bar[0].items[1].checked);
+        // Unchanged handlers keep their ids
+        assert_eq!(bar[0].items[0].id, new.id);
+        HeadlessBackend::take_events();
+        harness.rerender();
+        assert!(!HeadlessBackend::take_events()
+            .iter()
+            .any(|event| matches!(event, HeadlessEvent::SetMenuBar { .. })));
+    }
+
+    #[test]
+    fn routes_context_menu_items_to_the_component() {
+        let harness = TestHarness::<Notes>::new(());
+        harness.choose_menu_item("File", "New");
+        harness.choose_menu_item("File", "New");
+        harness.choose_menu_item("File", "New");
+        let second = harness.find_label_by_text("Note 2");
+        harness.choose_context_menu_item(&second, "Delete");
+        assert_eq!(
+            harness
+                .find_all(WidgetKind::Label)
+                .iter()
+                .map(HeadlessNode::text)
+                .collect::<Vec<_>>(),
+            ["Note 1", "Note 3"]
+        );
+        // Note 3 moved up a place so its menu was rebound to delete the note at its new index
+        let third = harness.find_label_by_text("Note 3");
+        assert_eq!(
+            third.borrow().context_menu.as_ref().unwrap().title,
+            "Note 3"
+        );
+        harness.choose_context_menu_item(&third, "Delete");
+        harness.find_label_by_text("Note 1");
+        assert_eq!(harness.find_all(WidgetKind::Label).len(), 1);
+        assert_eq!(harness.component().handler_count(), 3);
+    }
+}
@@ -0,0 +1,522 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    rc::Rc,
+};
+
+use crate::{Component, ContainerKind, VAttributes, VContainer, VDomDiff, VNode};
+
+/// Identifies a node among its siblings across renders
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Key {
+    /// Given to nodes rendered without a key, from where they sit among their siblings
+    Position(usize),
+    /// Given with `VNode::key`, it follows the node wherever it is rendered
+    Explicit(KeyValue),
+}
+
+impl Key {
+    pub fn explicit(value: impl Hash + Eq + fmt::Debug + 'static) -> Self {
+        Self::Explicit(KeyValue::new(value))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Position(position) => write!(f, "{position}"),
+            Key::Explicit(key) => write!(f, "key={key:?}"),
+        }
+    }
+}
+
+/// A key built from any hashable value such as a string, a database id or a tuple of them.
+/// Values of different types never match, so `1_u32` and `1_usize` are different keys
+#[derive(Clone)]
+pub struct KeyValue(Rc<dyn AnyKey>);
+
+impl KeyValue {
+    pub fn new(value: impl Hash + Eq + fmt::Debug + 'static) -> Self {
+        Self(Rc::new(value))
+    }
+
+    /// The value the key was built from, if it is a `K`
+    pub fn downcast_ref<K: 'static>(&self) -> Option<&K> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl PartialEq for KeyValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.equal_to(other.0.as_any())
+    }
+}
+
+impl Eq for KeyValue {}
+
+impl Hash for KeyValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash_into(state)
+    }
+}
+
+impl fmt::Debug for KeyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_debug(f)
+    }
+}
+
+/// Object safe versions of the traits a key value needs
+trait AnyKey {
+    fn as_any(&self) -> &dyn Any;
+    fn equal_to(&self, other: &dyn Any) -> bool;
+    fn hash_into(&self, state: &mut dyn Hasher);
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+impl<K: Hash + Eq + fmt::Debug + 'static> AnyKey for K {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn equal_to(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<K>() == Some(self)
+    }
+
+    fn hash_into(&self, mut state: &mut dyn Hasher) {
+        TypeId::of::<K>().hash(&mut state);
+        self.hash(&mut state);
+    }
+
+    fn fmt_debug(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Pairs each rendered node with its key, using the nodes position among its siblings when it wasn't given one.
+/// Two siblings with the same explicit key panic in debug builds, release builds fall back to the position of the later one
+pub fn assign_keys<T: Component + ?Sized>(nodes: Vec<VNode<T>>) -> Vec<(Key, VNode<T>)> {
+    let mut seen = HashSet::new();
+    nodes
+        .into_iter()
+        .enumerate()
+        .map(|(position, node)| {
+            let (key, node) = match node {
+                VNode::Keyed(key, node) => (Key::Explicit(key), *node),
+                node => (Key::Position(position), node),
+            };
+            if seen.insert(key.clone()) {
+                return (key, node);
+            }
+            if cfg!(debug_assertions) {
+                panic!(
+                    "{} rendered more than one child with the key {key}, keys must be unique among siblings",
+                    type_name::<T>()
+                );
+            }
+            (Key::Position(position), node)
+        })
+        .collect()
+}
+
+/// A change to one of the children of a component
+pub struct Patch<T: Component> {
+    pub key: Key,
+    /// Where the node sits among its siblings once every patch is applied, or where it was for removals
+    pub index: usize,
+    /// The sibling the node sits directly in front of once every patch is applied, `None` when it is the last child.
+    /// This is where inserted and moved nodes are placed
+    pub before: Option<Key>,
+    pub diff: VDomDiff<T>,
+}
+
+/// Works out the patches that turn the previously rendered children of a component into the new ones without touching any widgets.
+/// Applying the patches one after another to the old children gives exactly the new children:
+/// - Removals come first, from the back so their indexes stay valid.
+/// - Then inserts and moves, from the last child to the first, each placing its node in front of `before` which is already where it belongs.
+///   Nodes on the longest run that kept its relative order stay put, so swapping two rows moves one node rather than every node between them.
+/// - Then changes to the nodes themselves, by which point every `index` is the nodes final position
+pub fn reconcile<T: Component>(
+    old: &[(Key, VNode<T>)],
+    new: Vec<(Key, VNode<T>)>,
+) -> Vec<Patch<T>> {
+    let mut patches = Vec::new();
+    let new_keys = new.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>();
+    let new_key_set = new_keys.iter().collect::<HashSet<_>>();
+    for (index, (key, _)) in old.iter().enumerate().rev() {
+        if !new_key_set.contains(key) {
+            patches.push(Patch {
+                key: key.clone(),
+                index,
+                before: None,
+                diff: VDomDiff::Remove,
+            });
+        }
+    }
+    // Where each kept node sits once the removals are done
+    let old_positions = old
+        .iter()
+        .filter(|(key, _)| new_key_set.contains(key))
+        .enumerate()
+        .map(|(position, (key, node))| (key.clone(), (position, node)))
+        .collect::<HashMap<_, _>>();
+    let sources = new_keys
+        .iter()
+        .map(|key| old_positions.get(key).map(|(position, _)| *position))
+        .collect::<Vec<_>>();
+    let stable = longest_increasing_subsequence(&sources);
+    let mut new_nodes = new
+        .into_iter()
+        .map(|(_, node)| Some(node))
+        .collect::<Vec<_>>();
+    for index in (0..new_keys.len()).rev() {
+        let diff = match sources[index] {
+            None => VDomDiff::InsertNode(new_nodes[index].take().unwrap()),
+            Some(_) if !stable.contains(&index) => VDomDiff::Move,
+            Some(_) => continue,
+        };
+        patches.push(Patch {
+            key: new_keys[index].clone(),
+            index,
+            before: new_keys.get(index + 1).cloned(),
+            diff,
+        });
+    }
+    for (index, node) in new_nodes.into_iter().enumerate() {
+        let (Some(node), Some((_, existing))) = (node, old_positions.get(&new_keys[index])) else {
+            continue;
+        };
+        patches.extend(diff_nodes(existing, node).into_iter().map(|diff| Patch {
+            key: new_keys[index].clone(),
+            index,
+            before: new_keys.get(index + 1).cloned(),
+            diff,
+        }));
+    }
+    patches
+}
+
+/// The indexes of the longest run of entries whose values strictly increase, skipping `None`s
+fn longest_increasing_subsequence(values: &[Option<usize>]) -> HashSet<usize> {
+    // tails[length] is the index of the smallest value ending an increasing run of length + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+    for (index, value) in values.iter().enumerate() {
+        let Some(value) = value else {
+            continue;
+        };
+        let length = tails.partition_point(|tail| values[*tail].unwrap() < *value);
+        previous[index] = length.checked_sub(1).map(|length| tails[length]);
+        if length == tails.len() {
+            tails.push(index);
+        } else {
+            tails[length] = index;
+        }
+    }
+    let mut run = HashSet::new();
+    let mut current = tails.last().copied();
+    while let Some(index) = current {
+        run.insert(index);
+        current = previous[index];
+    }
+    run
+}
+
+/// The changes needed to turn one node into another in place, or a replacement when that isn't possible
+pub fn diff_nodes<T: Component>(a: &VNode<T>, b: VNode<T>) -> Vec<VDomDiff<T>> {
+    match (a, b) {
+        (VNode::Label(a), VNode::Label(b)) => {
+            if a.text != b.text {
+                vec![VDomDiff::UpdateLabelText(b.text)]
+            } else {
+                Vec::new()
+            }
+        }
+        (VNode::Text(a), VNode::Text(b)) => {
+            if *a != b {
+                vec![VDomDiff::UpdatePureText(b)]
+            } else {
+                Vec::new()
+            }
+        }
+        (VNode::Button(a), VNode::Button(b)) => {
+            let mut changes = Vec::new();
+            if a.text != b.text {
+                changes.push(VDomDiff::UpdateButtonText(b.text))
+            }
+            if a.click != b.click {
+                changes.push(VDomDiff::UpdateButtonClick(b.click))
+            }
+            changes
+        }
+        (VNode::TextInput(a), VNode::TextInput(b)) => {
+            let mut changes = Vec::new();
+            if a.initial_value != b.initial_value {
+                changes.push(VDomDiff::UpdateInputValue(b.initial_value))
+            }
+            if a.change != b.change {
+                changes.push(VDomDiff::UpdateInputChange(b.change))
+            }
+            changes
+        }
+        (VNode::Select(a), VNode::Select(b)) => {
+            let mut changes = Vec::new();
+            if a.options != b.options {
+                changes.push(VDomDiff::UpdateSelectOptions(b.options))
+            }
+            if a.selected != b.selected {
+                changes.push(VDomDiff::UpdateSelection(b.selected))
+            }
+            if a.select != b.select {
+                changes.push(VDomDiff::UpdateSelectHandler(b.select))
+            }
+            changes
+        }
+        // Rows are rendered from the props and state as well as the list, so they are rendered again whenever the component is
+        (VNode::List(_), VNode::List(b)) => vec![VDomDiff::UpdateList(b)],
+        (VNode::Container(a), VNode::Container(b))
+            if mem::discriminant(&a.kind) == mem::discriminant(&b.kind) =>
+        {
+            let VContainer { kind, children } = b;
+            let mut changes = Vec::new();
+            if let ContainerKind::Box(title) = kind {
+                if a.kind != ContainerKind::Box(title.clone()) {
+                    changes.push(VDomDiff::UpdateTitle(title))
+                }
+            }
+            let patches = reconcile(&a.children, children);
+            if !patches.is_empty() {
+                changes.push(VDomDiff::UpdateChildren(patches))
+            }
+            changes
+        }
+        (VNode::Custom(a), VNode::Custom(b)) => {
+            if *a == b {
+                Vec::new()
+            } else if a.type_id == b.type_id {
+                vec![VDomDiff::UpdatePropsFrom(b)]
+            } else {
+                // Both are custom components but different kinds so we must replace it
+                vec![VDomDiff::ReplaceWith(VNode::Custom(b))]
+            }
+        }
+        (a, b) if matches!(a, VNode::With(..)) || matches!(b, VNode::With(..)) => {
+            diff_attributes(a, b)
+        }
+        (_, b) => vec![VDomDiff::ReplaceWith(b)],
+    }
+}
+
+/// Diffs two nodes either of which was given attributes, a node without any has the default ones
+fn diff_attributes<T: Component>(a: &VNode<T>, b: VNode<T>) -> Vec<VDomDiff<T>> {
+    let none = VAttributes::default();
+    let (a_attributes, a) = match a {
+        VNode::With(attributes, node) => (attributes, &**node),
+        node => (&none, node),
+    };
+    let (b_attributes, b) = match b {
+        VNode::With(attributes, node) => (attributes, *node),
+        node => (VAttributes::default(), node),
+    };
+    let mut changes = diff_nodes(a, b);
+    if let [VDomDiff::ReplaceWith(_)] = changes.as_slice() {
+        let Some(VDomDiff::ReplaceWith(node)) = changes.pop() else {
+            unreachable!()
+        };
+        let node = if b_attributes == none {
+            node
+        } else {
+            VNode::With(b_attributes, Box::new(node))
+        };
+        return vec![VDomDiff::ReplaceWith(node)];
+    }
+    if a_attributes.context_menu != b_attributes.context_menu {
+        changes.push(VDomDiff::UpdateContextMenu(b_attributes.context_menu))
+    }
+    if a_attributes.on_key_down != b_attributes.on_key_down {
+        changes.push(VDomDiff::UpdateKeyDown(b_attributes.on_key_down))
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VLabel;
+
+    #[derive(PartialEq, Clone)]
+    struct Empty;
+
+    impl Component for Empty {
+        type Props = ();
+        type State = ();
+        fn render(_props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            Vec::new()
+        }
+    }
+
+    fn label(text: &str) -> VNode<Empty> {
+        VNode::Label(VLabel {
+            text: text.to_string(),
+        })
+    }
+
+    fn nodes(keys: &[usize]) -> Vec<(Key, VNode<Empty>)> {
+        assign_keys(
+            keys.iter()
+                .map(|key| label(&key.to_string()).key(*key))
+                .collect(),
+        )
+    }
+
+    /// Applies the patches to a list of keys following the rules `reconcile` documents
+    fn apply(old: &[usize], patches: &[Patch<Empty>]) -> Vec<usize> {
+        let mut keys = old
+            .iter()
+            .map(|key| Key::explicit(*key))
+            .collect::<Vec<_>>();
+        let place = |keys: &mut Vec<Key>, patch: &Patch<Empty>| {
+            let position = match &patch.before {
+                Some(before) => keys.iter().position(|key| key == before).unwrap(),
+                None => keys.len(),
+            };
+            keys.insert(position, patch.key.clone());
+        };
+        for patch in patches {
+            match patch.diff {
+                VDomDiff::Remove => assert_eq!(keys.remove(patch.index), patch.key),
+                VDomDiff::InsertNode(_) => place(&mut keys, patch),
+                VDomDiff::Move => {
+                    keys.retain(|key| *key != patch.key);
+                    place(&mut keys, patch);
+                }
+                _ => assert_eq!(keys[patch.index], patch.key),
+            }
+        }
+        keys.into_iter()
+            .map(|key| match key {
+                Key::Explicit(key) => *key.downcast_ref::<usize>().unwrap(),
+                Key::Position(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn moves(patches: &[Patch<Empty>]) -> Vec<Key> {
+        patches
+            .iter()
+            .filter(|patch| matches!(patch.diff, VDomDiff::Move))
+            .map(|patch| patch.key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_children_produce_no_patches() {
+        assert!(reconcile(&nodes(&[0, 1, 2]), nodes(&[0, 1, 2])).is_empty());
+    }
+
+    #[test]
+    fn updates_removes_and_inserts() {
+        let patches = reconcile(
+            &nodes(&[0, 1, 2]),
+            assign_keys(vec![
+                label("changed").key(0_usize),
+                label("2").key(2_usize),
+                label("3").key(3_usize),
+            ]),
+        );
+        let summary = patches
+            .iter()
+            .map(|patch| match &patch.diff {
+                VDomDiff::Remove => format!("remove {} at {}", patch.key, patch.index),
+                VDomDiff::InsertNode(_) => format!("insert {} at {}", patch.key, patch.index),
+                VDomDiff::UpdateLabelText(text) => format!("label {} to {text}", patch.key),
+                _ => "other".to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                "remove key=1 at 1",
+                "insert key=3 at 2",
+                "label key=0 to changed"
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_as_few_nodes_as_possible() {
+        assert_eq!(
+            moves(&reconcile(&nodes(&[0, 1, 2, 3]), nodes(&[1, 2, 3, 0]))),
+            [Key::explicit(0_usize)]
+        );
+        assert_eq!(
+            moves(&reconcile(&nodes(&[0, 1, 2, 3]), nodes(&[3, 0, 1, 2]))),
+            [Key::explicit(3_usize)]
+        );
+        assert_eq!(
+            moves(&reconcile(&nodes(&[0, 1, 2, 3]), nodes(&[0, 2, 1, 3]))).len(),
+            1
+        );
+        assert_eq!(
+            moves(&reconcile(&nodes(&[0, 1, 2]), nodes(&[2, 1, 0]))).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn applying_patches_produces_the_new_order() {
+        // A small linear congruential generator keeps this deterministic without extra dependencies
+        let mut seed = 0x2545_f491_u64;
+        let mut next = move |bound: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for _ in 0..500 {
+            let old = (0..next(8)).filter(|_| next(3) > 0).collect::<Vec<_>>();
+            let mut new = (0..10).filter(|_| next(2) > 0).collect::<Vec<_>>();
+            for index in (1..new.len()).rev() {
+                new.swap(index, next(index + 1));
+            }
+            let patches = reconcile(&nodes(&old), nodes(&new));
+            assert_eq!(apply(&old, &patches), new, "from {old:?}");
+        }
+    }
+
+    #[test]
+    fn unkeyed_nodes_are_keyed_by_position() {
+        let keys = assign_keys(vec![label("a"), label("b").key(7), label("c")])
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [Key::Position(0), Key::explicit(7), Key::Position(2)]);
+    }
+
+    #[test]
+    fn keys_can_be_any_hashable_value() {
+        let rows = |names: &[&'static str]| {
+            assign_keys(
+                names
+                    .iter()
+                    .map(|name| label(name).key(*name))
+                    .chain([label("total").key(("total", 1))])
+                    .collect(),
+            )
+        };
+        let patches = reconcile(&rows(&["ann", "bob", "cat"]), rows(&["cat", "ann", "bob"]));
+        assert_eq!(moves(&patches), [Key::explicit("cat")]);
+        assert_ne!(Key::explicit(1_u32), Key::explicit(1_usize));
+        assert_eq!(Key::explicit("ann").to_string(), "key=\"ann\"");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Empty rendered more than one child with the key key=1")]
+    fn duplicate_keys_panic_in_debug_builds() {
+        assign_keys(vec![label("a").key(1), label("b").key(1)]);
+    }
+}
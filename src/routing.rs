@@ -0,0 +1,142 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+
+use crate::Message;
+
+type Route = Rc<dyn Fn(&Message)>;
+
+thread_local! {
+    /// The component each registered handler id belongs to, messages from native widgets all arrive on the main thread
+    static ROUTES: RefCell<HashMap<usize, Route>> = RefCell::new(HashMap::new());
+    /// Callback events waiting for the handlers running to finish
+    static EVENTS: RefCell<VecDeque<Box<dyn FnOnce()>>> = RefCell::new(VecDeque::new());
+    /// How many handlers are running, events are held until there are none
+    static HOLDING: Cell<usize> = const { Cell::new(0) };
+    /// Whether the key press being routed has been handled
+    static KEY_HANDLED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Delivers a click, change or select straight to the component whose handler has the messages id.
+/// Returns false when no mounted component has one, such as for a widget that has since been removed
+pub fn route(message: &Message) -> bool {
+    // Cloned out so handling the message can register and unregister routes
+    let Some(route) = ROUTES.with(|routes| routes.borrow().get(&message.id).cloned()) else {
+        return false;
+    };
+    route(message);
+    true
+}
+
+/// Delivers a key press the same way as `route`, returning whether a key handler or shortcut took it.
+/// Ones that weren't can be left to the system, such as a text field typing the character
+pub fn route_key(message: &Message) -> bool {
+    KEY_HANDLED.with(|handled| handled.set(false));
+    route(message);
+    KEY_HANDLED.with(Cell::take)
+}
+
+pub(crate) fn key_handled() {
+    KEY_HANDLED.with(|handled| handled.set(true))
+}
+
+pub(crate) fn add_route(id: usize, route: Route) {
+    ROUTES.with(|routes| routes.borrow_mut().insert(id, route));
+}
+
+pub(crate) fn remove_route(id: usize) {
+    // Components can outlive the registry when they are dropped as the thread exits
+    let _ = ROUTES.try_with(|routes| routes.borrow_mut().remove(&id));
+}
+
+/// Runs a handler while it has the state of its component borrowed, holding back events it emits until it is done
+pub(crate) fn hold_events<R>(handler: impl FnOnce() -> R) -> R {
+    HOLDING.with(|holding| holding.set(holding.get() + 1));
+    let result = handler();
+    HOLDING.with(|holding| holding.set(holding.get() - 1));
+    deliver_events();
+    result
+}
+
+pub(crate) fn queue_event(event: Box<dyn FnOnce()>) {
+    EVENTS.with(|events| events.borrow_mut().push_back(event));
+    deliver_events();
+}
+
+fn deliver_events() {
+    if HOLDING.with(Cell::get) > 0 {
+        return;
+    }
+    while let Some(event) = EVENTS.with(|events| events.borrow_mut().pop_front()) {
+        event()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        flush, ClickHandler, Component, HeadlessBackend, HeadlessEvent, TestHarness, VButton,
+        VComponent, VLabel, VNode,
+    };
+
+    #[derive(PartialEq, Clone)]
+    struct Tally;
+
+    impl Component for Tally {
+        type Props = ();
+        type State = u32;
+        type Message = u32;
+        fn render(_props: &Self::Props, count: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, count| *count += 1)),
+                    text: "Add".to_string(),
+                }),
+                VNode::Label(VLabel {
+                    text: count.to_string(),
+                }),
+            ]
+        }
+        fn on_message(amount: &u32, _props: &Self::Props, count: &mut Self::State) -> bool {
+            *count += amount;
+            true
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Board;
+
+    impl Component for Board {
+        type Props = ();
+        type State = ();
+        fn render(_props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::group(vec![VNode::Custom(VComponent::new::<Tally>(
+                (),
+            ))])]
+        }
+    }
+
+    #[test]
+    fn routes_clicks_to_the_component_that_rendered_the_button() {
+        let harness = TestHarness::<Board>::new(());
+        let board = harness.root().id();
+        HeadlessBackend::take_events();
+        harness.click(&harness.find_button("Add"));
+        harness.find_label_by_text("1");
+        let laid_out = HeadlessBackend::take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                HeadlessEvent::Layout { parent, .. } => Some(parent),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Only the tally renders again
+        assert_eq!(laid_out.len(), 1);
+        assert_ne!(laid_out[0], board);
+        harness.component().broadcast(5_u32);
+        flush();
+        harness.find_label_by_text("6");
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+};
+
+use crate::Backend;
+
+/// A component that can be marked as needing to render again
+pub(crate) trait Scheduled {
+    /// How many components the component is nested in, parents render before their children
+    fn depth(&self) -> usize;
+    /// Renders the component if nothing has rendered it since it was marked
+    fn render_if_dirty(self: Rc<Self>);
+}
+
+thread_local! {
+    static DIRTY: RefCell<Vec<Weak<dyn Scheduled>>> = const { RefCell::new(Vec::new()) };
+    /// Effects waiting for the components to render
+    static AFTER_FLUSH: RefCell<Vec<Box<dyn FnOnce()>>> = const { RefCell::new(Vec::new()) };
+    /// Whether the backend has been asked to flush already
+    static SCHEDULED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Queues a component to render on the next `flush`, asking the backend for one if there isn't one coming
+pub(crate) fn schedule<B: Backend>(component: Weak<dyn Scheduled>) {
+    DIRTY.with(|dirty| dirty.borrow_mut().push(component));
+    request_flush::<B>()
+}
+
+/// Runs an effect at the end of the next `flush`, once everything has rendered
+pub(crate) fn after_flush<B: Backend>(effect: Box<dyn FnOnce()>) {
+    AFTER_FLUSH.with(|effects| effects.borrow_mut().push(effect));
+    request_flush::<B>()
+}
+
+fn request_flush<B: Backend>() {
+    if !SCHEDULED.with(|scheduled| scheduled.replace(true)) {
+        B::schedule_flush()
+    }
+}
+
+/// Renders every component that has changed since the last flush.
+/// Components are rendered outermost first so a component whose parent gives it new props only renders once, with those props.
+/// Backends call this once the event that changed them has been handled, tests can call it directly
+pub fn flush() {
+    SCHEDULED.with(|scheduled| scheduled.set(false));
+    loop {
+        while let Some(component) = next_dirty() {
+            component.render_if_dirty()
+        }
+        let effects = AFTER_FLUSH.with(|effects| effects.take());
+        if effects.is_empty() {
+            break;
+        }
+        for effect in effects {
+            effect()
+        }
+    }
+}
+
+/// The outermost component waiting to render
+fn next_dirty() -> Option<Rc<dyn Scheduled>> {
+    DIRTY.with(|dirty| {
+        let mut dirty = dirty.borrow_mut();
+        // Components that have since been dropped don't need rendering
+        dirty.retain(|component| component.strong_count() > 0);
+        let next = dirty
+            .iter()
+            .enumerate()
+            .filter_map(|(index, component)| Some((index, component.upgrade()?.depth())))
+            .min_by_key(|(_, depth)| *depth)?
+            .0;
+        dirty.swap_remove(next).upgrade()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{flush, Component, HeadlessBackend, HeadlessEvent, TestHarness, VLabel, VNode};
+
+    #[derive(PartialEq, Clone)]
+    struct Sum;
+
+    impl Component for Sum {
+        type Props = ();
+        type State = u32;
+        type Message = u32;
+        fn render(_props: &Self::Props, total: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::Label(VLabel {
+                text: total.to_string(),
+            })]
+        }
+        fn on_message(amount: &u32, _props: &Self::Props, total: &mut Self::State) -> bool {
+            *total += amount;
+            true
+        }
+    }
+
+    #[test]
+    fn renders_once_per_flush() {
+        let harness = TestHarness::<Sum>::new(());
+        HeadlessBackend::take_events();
+        for amount in 1..=3_u32 {
+            harness.component().broadcast(amount);
+        }
+        assert_eq!(HeadlessBackend::take_events(), []);
+        flush();
+        harness.find_label_by_text("6");
+        let laid_out = HeadlessBackend::take_events()
+            .into_iter()
+            .filter(|event| matches!(event, HeadlessEvent::Layout { .. }))
+            .count();
+        assert_eq!(laid_out, 1);
+    }
+}
@@ -0,0 +1,318 @@
+use std::{fmt::Write, fs, path::Path};
+
+use crate::{Component, ContainerKind, Key, Renderable, VNode};
+
+/// Dumps the virtual DOM of a mounted component and all of its nested components as indented text, one node per line.
+/// Nodes are listed by key so the output only changes when what was rendered changes
+pub fn snapshot(component: &dyn Renderable) -> String {
+    let mut out = format!("{}\n", component.name());
+    component.write_snapshot(1, &mut out);
+    out
+}
+
+/// Compares a snapshot with the one saved at `path`, panicking with a line diff if they differ.
+/// Missing snapshots fail as well so a deleted or misnamed one can't pass unnoticed.
+/// Setting `UPDATE_SNAPSHOTS=1` writes out missing snapshots and overwrites any that don't match
+pub fn assert_snapshot_matches(path: &Path, actual: &str) {
+    compare_snapshot(path, actual, std::env::var_os("UPDATE_SNAPSHOTS").is_some())
+}
+
+/// `assert_snapshot_matches` with whether to write out snapshots given rather than read from the environment
+fn compare_snapshot(path: &Path, actual: &str, update: bool) {
+    let Ok(expected) = fs::read_to_string(path) else {
+        assert!(
+            update,
+            "Snapshot {} is missing, rerun with UPDATE_SNAPSHOTS=1 to write it",
+            path.display()
+        );
+        write_snapshot_file(path, actual);
+        eprintln!("Wrote new snapshot {}", path.display());
+        return;
+    };
+    if expected == actual {
+        return;
+    }
+    if update {
+        write_snapshot_file(path, actual);
+        eprintln!("Updated snapshot {}", path.display());
+        return;
+    }
+    panic!(
+        "Snapshot {} does not match, rerun with UPDATE_SNAPSHOTS=1 to accept the changes\n{}",
+        path.display(),
+        line_diff(&expected, actual)
+    );
+}
+
+/// Snapshots a mounted component and compares it with `tests/snapshots/<name>.snap` in the crate being tested
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($name:expr, $component:expr) => {
+        $crate::assert_snapshot_matches(
+            ::std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots"))
+                .join(format!("{}.snap", $name))
+                .as_path(),
+            &$crate::snapshot($component),
+        )
+    };
+}
+
+pub(crate) fn write_node<T: Component + ?Sized>(
+    node: &VNode<T>,
+    key: Option<&Key>,
+    props: &T::Props,
+    state: &T::State,
+    depth: usize,
+    out: &mut String,
+) {
+    if let VNode::Keyed(key, node) = node {
+        // Only list rows still hold their keys, the rest are taken out when the component renders
+        return write_node(
+            node,
+            Some(&Key::Explicit(key.clone())),
+            props,
+            state,
+            depth,
+            out,
+        );
+    }
+    if let VNode::With(attributes, node) = node {
+        write_node(node, key, props, state, depth, out);
+        let indent = "  ".repeat(depth + 1);
+        if let Some(menu) = &attributes.context_menu {
+            let items = menu
+                .items
+                .iter()
+                .map(|item| item.title.as_str())
+                .collect::<Vec<_>>();
+            writeln!(out, "{indent}menu {:?} {items:?}", menu.title).unwrap();
+        }
+        if attributes.on_key_down.is_some() {
+            writeln!(out, "{indent}on:key_down").unwrap();
+        }
+        if attributes.autofocus {
+            writeln!(out, "{indent}autofocus").unwrap();
+        }
+        return;
+    }
+    out.push_str(&"  ".repeat(depth));
+    if let Some(key) = key {
+        write!(out, "[{key}] ").unwrap();
+    } else {
+        out.push_str("- ");
+    }
+    match node {
+        VNode::Label(label) => writeln!(out, "Label {:?}", label.text),
+        VNode::Text(text) => writeln!(out, "Text {text:?}"),
+        VNode::Button(button) => writeln!(
+            out,
+            "Button {:?}{}",
+            button.text,
+            handler_marker(button.click.is_some(), "click")
+        ),
+        VNode::TextInput(input) => writeln!(
+            out,
+            "TextInput {:?}{}",
+            input.initial_value,
+            handler_marker(input.change.is_some(), "change")
+        ),
+        VNode::Select(select) => writeln!(
+            out,
+            "Select {:?}{}{}",
+            select.options,
+            select
+                .selected
+                .map(|index| format!(" selected:{index}"))
+                .unwrap_or_default(),
+            handler_marker(select.select.is_some(), "select")
+        ),
+        VNode::List(list) => {
+            writeln!(out, "List").unwrap();
+            for index in 0..list.count {
+                writeln!(out, "{}row {index}", "  ".repeat(depth + 1)).unwrap();
+                for row_node in (list.render)(index, props, state) {
+                    write_node(&row_node, None, props, state, depth + 2, out);
+                }
+            }
+            Ok(())
+        }
+        VNode::Custom(component) => {
+            writeln!(out, "Custom {}", component.renderable().name()).unwrap();
+            component.renderable().write_snapshot(depth + 1, out);
+            Ok(())
+        }
+        VNode::Container(container) => {
+            match &container.kind {
+                ContainerKind::Stack => writeln!(out, "Stack"),
+                ContainerKind::Group => writeln!(out, "Group"),
+                ContainerKind::Box(title) => writeln!(out, "Box {title:?}"),
+            }
+            .unwrap();
+            for (key, child) in container.children() {
+                write_node(child, Some(key), props, state, depth + 1, out);
+            }
+            Ok(())
+        }
+        VNode::Keyed(..) | VNode::With(..) => unreachable!(),
+    }
+    .unwrap()
+}
+
+/// The name of a type without the path of the module it lives in
+pub(crate) fn short_type_name(name: &'static str) -> &'static str {
+    let path_end = name.find('<').unwrap_or(name.len());
+    match name[..path_end].rfind("::") {
+        Some(index) => &name[index + 2..],
+        None => name,
+    }
+}
+
+fn handler_marker(present: bool, name: &str) -> String {
+    if present {
+        format!(" on:{name}")
+    } else {
+        String::new()
+    }
+}
+
+fn write_snapshot_file(path: &Path, contents: &str) {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).expect("Could not create snapshot directory");
+    }
+    fs::write(path, contents).expect("Could not write snapshot");
+}
+
+/// A minimal line based diff, good enough to spot which node changed
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    // Longest common subsequence table, built from the end so it can be walked forwards
+    let mut lengths = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            writeln!(diff, "  {}", expected[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if j < actual.len()
+            && (i == expected.len() || lengths[i][j + 1] >= lengths[i + 1][j])
+        {
+            writeln!(diff, "+ {}", actual[j]).unwrap();
+            j += 1;
+        } else {
+            writeln!(diff, "- {}", expected[i]).unwrap();
+            i += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{ClickHandler, TestHarness, VButton, VComponent, VLabel, VList};
+
+    #[derive(PartialEq, Clone)]
+    struct Row;
+
+    impl Component for Row {
+        type Props = String;
+        type State = ();
+        fn render(props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::Label(VLabel {
+                text: props.clone(),
+            })]
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Shopping;
+
+    impl Component for Shopping {
+        type Props = Vec<&'static str>;
+        type State = bool;
+        fn render(props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, done| *done = true)),
+                    text: "Done".to_string(),
+                }),
+                VNode::Custom(VComponent::new::<Row>(
+                    if *state { "Finished" } else { "Shopping" }.to_string(),
+                ))
+                .key(1),
+                VNode::List(VList {
+                    count: props.len(),
+                    render: |index, props, _| vec![VNode::Text(props[index]), VNode::Text("each")],
+                }),
+            ]
+        }
+    }
+
+    #[test]
+    fn snapshots_nested_components_and_lists() {
+        let harness = TestHarness::<Shopping>::new(vec!["Milk", "Eggs"]);
+        assert_snapshot!("shopping_list", harness.component());
+        harness.click(&harness.find_button("Done"));
+        assert_snapshot!("shopping_list_done", harness.component());
+    }
+
+    #[test]
+    #[should_panic(expected = "is missing")]
+    fn fails_when_the_snapshot_is_missing() {
+        compare_snapshot(&missing_snapshot(), "Shopping\n", false);
+    }
+
+    #[test]
+    fn writes_missing_snapshots_when_updating() {
+        let path = missing_snapshot();
+        compare_snapshot(&path, "Shopping\n", true);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Shopping\n");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    /// A path in a directory of its own that nothing has been written to yet
+    fn missing_snapshot() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "cacao_framework_{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        assert!(
+            !dir.exists(),
+            "{} is left from an earlier run",
+            dir.display()
+        );
+        dir.join("missing.snap")
+    }
+
+    #[test]
+    fn strips_module_paths() {
+        assert_eq!(short_type_name("a::b::Row"), "Row");
+        assert_eq!(short_type_name("a::Wrapper<b::Row>"), "Wrapper<b::Row>");
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        assert_eq!(
+            line_diff("a\nb\nc", "a\nB\nc"),
+            "  a\n+ B\n- b\n  c\n".to_string()
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    any::Any,
+    cell::Cell,
+    collections::VecDeque,
+    fmt::Debug,
+    future::poll_fn,
+    hash::Hash,
+    marker::PhantomData,
+    rc::Rc,
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    task::{Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    executor::{cancel, spawn},
+    timer, Backend, KeyValue,
+};
+
+/// Something a component listens to for as long as `Component::subscriptions` returns it.
+/// Subscriptions are compared with the ones returned after the previous render like nodes are, new ones are started and ones that are gone are stopped, so one that is returned every render keeps running.
+/// Timers are told apart by their duration and message, other sources by the key they are given
+pub struct Subscription<M>(Source<M>);
+
+/// Starts an external source, returning what stops it when dropped and how to turn the values it sends into messages
+type Start<M> = Box<dyn FnOnce(Arc<Mutex<Inbox>>) -> (Box<dyn Any>, Box<dyn Fn(Value) -> M>)>;
+
+type Value = Box<dyn Any + Send>;
+
+/// How long the thread reading a channel waits for a value before checking whether the subscription has been stopped
+const CHANNEL_POLL: Duration = Duration::from_millis(50);
+
+enum Source<M> {
+    Timer {
+        delay: Duration,
+        repeat: bool,
+        message: M,
+    },
+    External {
+        key: KeyValue,
+        /// Taken when the source is started
+        start: Cell<Option<Start<M>>>,
+    },
+}
+
+impl<M> Subscription<M> {
+    /// Sends the message every `period`, starting one period after it is first subscribed to.
+    /// Ticks missed while the app was busy are sent as one, and periods shorter than a millisecond are treated as a millisecond
+    pub fn every(period: Duration, message: M) -> Self {
+        Self(Source::Timer {
+            delay: period,
+            repeat: true,
+            message,
+        })
+    }
+
+    /// Sends the message once, `delay` after it is first subscribed to.
+    /// It won't be sent again while it keeps being returned
+    pub fn after(delay: Duration, message: M) -> Self {
+        Self(Source::Timer {
+            delay,
+            repeat: false,
+            message,
+        })
+    }
+
+    /// Sends each value received on a channel to the component once mapped with `map`.
+    /// `open` is only called when the subscription starts, so a render that returns it again doesn't need a receiver of its own.
+    /// The channel is read on a thread of its own, which finishes once every sender has been dropped or soon after the subscription is stopped
+    pub fn channel<T: Send + 'static>(
+        key: impl Hash + Eq + Debug + 'static,
+        open: impl FnOnce() -> Receiver<T> + 'static,
+        map: impl Fn(T) -> M + 'static,
+    ) -> Self
+    where
+        M: 'static,
+    {
+        Self::custom(
+            key,
+            move |emitter| {
+                let receiver = open();
+                thread::spawn(move || loop {
+                    let running = match receiver.recv_timeout(CHANNEL_POLL) {
+                        Ok(value) => emitter.emit(value),
+                        Err(RecvTimeoutError::Timeout) => !emitter.is_stopped(),
+                        Err(RecvTimeoutError::Disconnected) => false,
+                    };
+                    if !running {
+                        break;
+                    }
+                });
+            },
+            map,
+        )
+    }
+
+    /// Starts a source of values such as a file watcher, which sends them to the component with the `Emitter` it is given.
+    /// Whatever `start` returns is dropped when the subscription is stopped, which is where a source can stop itself
+    pub fn custom<T: Send + 'static, G: 'static>(
+        key: impl Hash + Eq + Debug + 'static,
+        start: impl FnOnce(Emitter<T>) -> G + 'static,
+        map: impl Fn(T) -> M + 'static,
+    ) -> Self
+    where
+        M: 'static,
+    {
+        let start: Start<M> = Box::new(move |inbox| {
+            let guard = start(Emitter {
+                inbox,
+                value: PhantomData,
+            });
+            (
+                Box::new(guard),
+                Box::new(move |value: Value| map(*value.downcast().unwrap())),
+            )
+        });
+        Self(Source::External {
+            key: KeyValue::new(key),
+            start: Cell::new(Some(start)),
+        })
+    }
+
+    /// Starts the subscription, sending what it produces to `deliver`.
+    /// It is stopped when what this returns is dropped
+    pub(crate) fn start<B: Backend>(&self, deliver: Rc<dyn Fn(M)>) -> Box<dyn Any>
+    where
+        M: Clone + 'static,
+    {
+        match &self.0 {
+            Source::Timer {
+                delay,
+                repeat,
+                message,
+            } => {
+                let message = message.clone();
+                Box::new(timer::start::<B>(
+                    *delay,
+                    repeat.then_some(*delay),
+                    Rc::new(move || deliver(message.clone())),
+                ))
+            }
+            Source::External { start, .. } => {
+                let start = start
+                    .take()
+                    .expect("Subscriptions can only be started once");
+                let inbox = Arc::<Mutex<Inbox>>::default();
+                let (guard, map) = start(inbox.clone());
+                let shared = inbox.clone();
+                let task = spawn::<B>(poll_fn(move |context| {
+                    let values = {
+                        let mut inbox = shared.lock().unwrap();
+                        inbox.waker = Some(context.waker().clone());
+                        inbox.values.split_off(0)
+                    };
+                    for value in values {
+                        deliver(map(value))
+                    }
+                    // Only finishes when it is cancelled
+                    Poll::<()>::Pending
+                }));
+                Box::new(Running {
+                    task,
+                    inbox,
+                    _guard: guard,
+                })
+            }
+        }
+    }
+}
+
+/// Values sent by an external source waiting to be delivered on the main thread
+#[derive(Default)]
+struct Inbox {
+    values: VecDeque<Value>,
+    /// Wakes the task that delivers them
+    waker: Option<Waker>,
+    stopped: bool,
+}
+
+/// Sends values to the component subscribed to a source from any thread
+pub struct Emitter<T> {
+    inbox: Arc<Mutex<Inbox>>,
+    value: PhantomData<fn(T)>,
+}
+
+impl<T: Send + 'static> Emitter<T> {
+    /// Returns false once the subscription has been stopped, so sources on other threads know to finish
+    pub fn emit(&self, value: T) -> bool {
+        let waker = {
+            let mut inbox = self.inbox.lock().unwrap();
+            if inbox.stopped {
+                return false;
+            }
+            inbox.values.push_back(Box::new(value));
+            inbox.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake()
+        }
+        true
+    }
+
+    /// Whether the subscription has been stopped, for sources that wait for values to check now and then
+    pub fn is_stopped(&self) -> bool {
+        self.inbox.lock().unwrap().stopped
+    }
+}
+
+impl<T> Clone for Emitter<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inbox: self.inbox.clone(),
+            value: PhantomData,
+        }
+    }
+}
+
+/// Stops an external source when dropped
+struct Running {
+    task: usize,
+    inbox: Arc<Mutex<Inbox>>,
+    _guard: Box<dyn Any>,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.inbox.lock().unwrap().stopped = true;
+        cancel(self.task)
+    }
+}
+
+impl<M: PartialEq> PartialEq for Subscription<M> {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (
+                Source::Timer {
+                    delay,
+                    repeat,
+                    message,
+                },
+                Source::Timer {
+                    delay: other_delay,
+                    repeat: other_repeat,
+                    message: other_message,
+                },
+            ) => delay == other_delay && repeat == other_repeat && message == other_message,
+            (Source::External { key, .. }, Source::External { key: other, .. }) => key == other,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        sync::mpsc::{self, Sender},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        ClickHandler, Component, Emitter, Subscription, TestHarness, VButton, VLabel, VNode,
+        WidgetKind,
+    };
+
+    thread_local! {
+        static FEEDS: RefCell<Vec<Emitter<String>>> = const { RefCell::new(Vec::new()) };
+        static STOPPED_FEEDS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts the feeds that have been stopped when dropped
+    struct FeedGuard;
+
+    impl Drop for FeedGuard {
+        fn drop(&mut self) {
+            STOPPED_FEEDS.with(|stopped| stopped.set(stopped.get() + 1))
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Feed;
+
+    #[derive(PartialEq, Clone, Default)]
+    struct FeedState {
+        paused: bool,
+        lines: Vec<String>,
+    }
+
+    #[derive(PartialEq, Clone)]
+    enum FeedMessage {
+        Line(String),
+    }
+
+    impl Component for Feed {
+        type Props = ();
+        type State = FeedState;
+        type Message = FeedMessage;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, state: &mut FeedState| {
+                        state.lines.clear()
+                    })),
+                    text: "Clear".to_string(),
+                }),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, state: &mut FeedState| {
+                        state.paused = true
+                    })),
+                    text: "Pause".to_string(),
+                }),
+                VNode::Label(VLabel {
+                    text: state.lines.join(" "),
+                }),
+            ]
+        }
+        fn on_message(
+            FeedMessage::Line(line): &FeedMessage,
+            _props: &Self::Props,
+            state: &mut Self::State,
+        ) -> bool {
+            state.lines.push(line.clone());
+            true
+        }
+        fn subscriptions(
+            _props: &Self::Props,
+            state: &Self::State,
+        ) -> Vec<Subscription<FeedMessage>> {
+            if state.paused {
+                return Vec::new();
+            }
+            vec![Subscription::custom(
+                "feed",
+                |emitter| {
+                    FEEDS.with(|feeds| feeds.borrow_mut().push(emitter));
+                    FeedGuard
+                },
+                FeedMessage::Line,
+            )]
+        }
+    }
+
+    #[test]
+    fn keeps_external_subscriptions_running_until_they_are_dropped() {
+        let harness = TestHarness::<Feed>::new(());
+        let feed = FEEDS.with(|feeds| feeds.borrow()[0].clone());
+        assert!(feed.emit("a".to_string()));
+        assert!(feed.emit("b".to_string()));
+        harness.settle();
+        harness.find_label_by_text("a b");
+        harness.click(&harness.find_button("Clear"));
+        // Returned again with the same key so it keeps running
+        assert!(feed.emit("c".to_string()));
+        harness.settle();
+        harness.find_label_by_text("c");
+        assert_eq!(FEEDS.with(|feeds| feeds.borrow().len()), 1);
+        harness.click(&harness.find_button("Pause"));
+        assert_eq!(STOPPED_FEEDS.with(Cell::get), 1);
+        assert!(!feed.emit("d".to_string()));
+    }
+
+    thread_local! {
+        static SENDERS: RefCell<Vec<Sender<u32>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Meter;
+
+    #[derive(PartialEq, Clone, Default)]
+    struct MeterState {
+        stopped: bool,
+        total: u32,
+    }
+
+    impl Component for Meter {
+        type Props = ();
+        type State = MeterState;
+        type Message = u32;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, state: &mut MeterState| {
+                        state.stopped = true
+                    })),
+                    text: "Stop".to_string(),
+                }),
+                VNode::Label(VLabel {
+                    text: format!("Total {}", state.total),
+                }),
+            ]
+        }
+        fn on_message(amount: &u32, _props: &Self::Props, state: &mut Self::State) -> bool {
+            state.total += amount;
+            true
+        }
+        fn subscriptions(_props: &Self::Props, state: &Self::State) -> Vec<Subscription<u32>> {
+            if state.stopped {
+                return Vec::new();
+            }
+            vec![Subscription::channel(
+                "meter",
+                || {
+                    let (sender, receiver) = mpsc::channel();
+                    SENDERS.with(|senders| senders.borrow_mut().push(sender));
+                    receiver
+                },
+                |amount| amount,
+            )]
+        }
+    }
+
+    /// Settles until `done` holds, as values sent on a channel arrive from the thread reading it
+    fn wait_for(harness: &TestHarness<Meter>, done: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(start.elapsed() < Duration::from_secs(5), "Gave up waiting");
+            thread::sleep(Duration::from_millis(5));
+            harness.settle();
+        }
+    }
+
+    #[test]
+    fn opens_channels_once_and_stops_reading_them_when_stopped() {
+        let harness = TestHarness::<Meter>::new(());
+        let sender = SENDERS.with(|senders| senders.borrow()[0].clone());
+        sender.send(2).unwrap();
+        sender.send(3).unwrap();
+        wait_for(&harness, || {
+            harness.find_all(WidgetKind::Label)[0].text() == "Total 5"
+        });
+        // Rendering again returns the subscription without opening another channel
+        assert_eq!(SENDERS.with(|senders| senders.borrow().len()), 1);
+        harness.click(&harness.find_button("Stop"));
+        // The reading thread finishes and drops the receiver
+        let start = Instant::now();
+        while sender.send(1).is_ok() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "The channel is still read"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::time::Duration;
+
+use crate::{
+    flush, poll_tasks, route_key, Component, ComponentWrapper, HeadlessBackend, HeadlessNode,
+    KeyPress, Message, NativeMenu, Renderable, WidgetKind,
+};
+
+/// Mounts a component with the headless backend and lets tests find widgets and interact with them the way a user would.
+/// Interactions send the same messages the native widgets dispatch, so they go through `ComponentWrapper::on_message` exactly like they do in an app
+pub struct TestHarness<T: Component + Clone + PartialEq + 'static> {
+    component: ComponentWrapper<T, HeadlessBackend>,
+    root: HeadlessNode,
+}
+
+impl<T: Component + Clone + PartialEq + 'static> TestHarness<T> {
+    pub fn new(props: T::Props) -> Self {
+        let component = ComponentWrapper::new(props);
+        let root = HeadlessBackend::mount(&component);
+        Self { component, root }
+    }
+
+    pub fn component(&self) -> &ComponentWrapper<T, HeadlessBackend> {
+        &self.component
+    }
+
+    /// The container the component was mounted into
+    pub fn root(&self) -> &HeadlessNode {
+        &self.root
+    }
+
+    /// Every widget of the given kind in the order they appear on screen, followed by those in open windows
+    pub fn find_all(&self, kind: WidgetKind) -> Vec<HeadlessNode> {
+        std::iter::once(self.root.clone())
+            .chain(HeadlessBackend::windows())
+            .flat_map(|node| node.descendants())
+            .filter(|node| node.kind() == kind)
+            .collect()
+    }
+
+    /// Finds an open window by its title
+    pub fn find_window(&self, title: &str) -> HeadlessNode {
+        self.find(WidgetKind::Window, title)
+    }
+
+    pub fn find_button(&self, text: &str) -> HeadlessNode {
+        self.find(WidgetKind::Button, text)
+    }
+
+    pub fn find_label_by_text(&self, text: &str) -> HeadlessNode {
+        self.find(WidgetKind::Label, text)
+    }
+
+    /// Finds a text input by its current value
+    pub fn find_text_input(&self, value: &str) -> HeadlessNode {
+        self.find(WidgetKind::TextInput, value)
+    }
+
+    /// Finds a select that offers the given option
+    pub fn find_select(&self, option: &str) -> HeadlessNode {
+        self.find_all(WidgetKind::Select)
+            .into_iter()
+            .find(|node| node.borrow().options.iter().any(|opt| opt == option))
+            .unwrap_or_else(|| panic!("No select with the option {option:?} found"))
+    }
+
+    pub fn click(&self, button: &HeadlessNode) {
+        let id = Self::handler_of(button);
+        self.send(Message::click(id));
+    }
+
+    /// Types the text one character at a time, sending a change message with the new value after each one like a real text field does
+    pub fn type_into(&self, input: &HeadlessNode, text: &str) {
+        let id = Self::handler_of(input);
+        for character in text.chars() {
+            let mut value = input.text();
+            value.push(character);
+            input.set_value(&value);
+            self.send(Message::change(id, value));
+        }
+    }
+
+    pub fn choose(&self, select: &HeadlessNode, index: usize) {
+        let id = Self::handler_of(select);
+        let options = select.borrow().options.len();
+        assert!(
+            index < options,
+            "Tried to choose option {index} of a select with {options} options"
+        );
+        select.set_selected_by_user(index);
+        self.send(Message::select(id, index));
+    }
+
+    /// Chooses an item from a menu in the menu bar
+    pub fn choose_menu_item(&self, menu: &str, item: &str) {
+        let bar = HeadlessBackend::menu_bar();
+        let menu = bar
+            .iter()
+            .find(|other| other.title == menu)
+            .unwrap_or_else(|| panic!("No menu {menu:?} in the menu bar"));
+        self.choose_item(menu, item);
+    }
+
+    /// Chooses an item from the context menu of a widget
+    pub fn choose_context_menu_item(&self, node: &HeadlessNode, item: &str) {
+        let menu =
+            node.borrow().context_menu.clone().unwrap_or_else(|| {
+                panic!("{:?} {:?} has no context menu", node.kind(), node.text())
+            });
+        self.choose_item(&menu, item);
+    }
+
+    fn choose_item(&self, menu: &NativeMenu, item: &str) {
+        let item = menu
+            .items
+            .iter()
+            .find(|other| other.title == item)
+            .unwrap_or_else(|| panic!("No item {item:?} in the menu {:?}", menu.title));
+        assert!(
+            item.enabled,
+            "Tried to choose the disabled item {:?}",
+            item.title
+        );
+        if let Some(id) = item.id {
+            self.send(Message::click(id));
+        }
+    }
+
+    /// Closes a window with its close button
+    pub fn close_window(&self, window: &HeadlessNode) {
+        let closable = window
+            .borrow()
+            .window
+            .as_ref()
+            .is_some_and(|window| window.closable);
+        assert!(closable, "Window {:?} can't be closed", window.text());
+        let id = Self::handler_of(window);
+        HeadlessBackend::forget_window(window);
+        self.send(Message::close(id));
+    }
+
+    /// Moves keyboard focus to the widget like a user tabbing to it would
+    pub fn focus(&self, node: &HeadlessNode) {
+        HeadlessBackend::set_focus_by_user(node)
+    }
+
+    /// The widget with keyboard focus
+    pub fn focused(&self) -> Option<HeadlessNode> {
+        let focused = HeadlessBackend::focused()?;
+        std::iter::once(self.root.clone())
+            .chain(HeadlessBackend::windows())
+            .flat_map(|node| node.descendants())
+            .find(|node| node.id() == focused)
+    }
+
+    /// Presses a key while the widget has keyboard focus, returning whether a key handler or shortcut took it.
+    /// The key press goes to the nearest key handler of the widget or the views it is in, like it does in an app
+    pub fn press_key(&self, node: &HeadlessNode, press: KeyPress) -> bool {
+        let path = std::iter::once(self.root.clone())
+            .chain(HeadlessBackend::windows())
+            .find_map(|top| path_to(&top, node))
+            .unwrap_or_else(|| panic!("{:?} {:?} isn't on screen", node.kind(), node.text()));
+        let Some(id) = path.iter().rev().find_map(|node| node.borrow().key_handler) else {
+            return false;
+        };
+        let handled = route_key(&Message::key(id, press));
+        self.settle();
+        handled
+    }
+
+    /// Delivers a message to the component followed by anything that was dispatched while handling it.
+    /// Woken tasks are polled and changes are flushed after each round of messages like the run loop would
+    pub fn send(&self, message: Message) {
+        self.component.on_message(&message);
+        self.settle();
+    }
+
+    /// Moves the clock forward, firing any timers that become due, and settles
+    pub fn advance(&self, by: Duration) {
+        HeadlessBackend::advance_clock(by);
+        self.settle();
+    }
+
+    /// Polls woken tasks, flushes and delivers dispatched messages until there is nothing left to do.
+    /// Tests call this after waking a task themselves
+    pub fn settle(&self) {
+        loop {
+            let polled = poll_tasks();
+            flush();
+            let dispatched = HeadlessBackend::take_dispatched();
+            if !polled && dispatched.is_empty() {
+                break;
+            }
+            for message in dispatched {
+                self.component.on_message(&message);
+            }
+        }
+    }
+
+    /// Renders the component again without any message, for after changing something it reads from outside the framework
+    pub fn rerender(&self) {
+        self.component.render();
+    }
+
+    fn find(&self, kind: WidgetKind, text: &str) -> HeadlessNode {
+        self.find_all(kind)
+            .into_iter()
+            .find(|node| node.text() == text)
+            .unwrap_or_else(|| panic!("No {kind:?} with the text {text:?} found"))
+    }
+
+    fn handler_of(node: &HeadlessNode) -> usize {
+        node.handler()
+            .unwrap_or_else(|| panic!("{:?} {:?} has no handler", node.kind(), node.text()))
+    }
+}
+
+/// The widgets from `from` down to `node`, both included
+fn path_to(from: &HeadlessNode, node: &HeadlessNode) -> Option<Vec<HeadlessNode>> {
+    if from.id() == node.id() {
+        return Some(vec![node.clone()]);
+    }
+    let children = from.borrow().children.clone();
+    children.iter().find_map(|child| {
+        let mut path = path_to(child, node)?;
+        path.insert(0, from.clone());
+        Some(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChangeHandler, SelectHandler, VLabel, VNode, VSelect, VTextInput};
+
+    #[derive(PartialEq, Clone)]
+    struct Form;
+
+    #[derive(PartialEq, Clone, Default)]
+    struct FormState {
+        name: String,
+        colour: Option<usize>,
+    }
+
+    impl Component for Form {
+        type Props = ();
+        type State = FormState;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::TextInput(VTextInput {
+                    change: Some(ChangeHandler::<Self>::new(|value, _, state| {
+                        state.name = value.to_string();
+                        true
+                    })),
+                    initial_value: state.name.clone(),
+                }),
+                VNode::Select(VSelect {
+                    options: vec!["Red".to_string(), "Green".to_string()],
+                    selected: state.colour,
+                    select: Some(SelectHandler::<Self>::new(|index, _, state| {
+                        state.colour = Some(index);
+                        true
+                    })),
+                }),
+                VNode::Label(VLabel {
+                    text: format!("{} {:?}", state.name, state.colour),
+                }),
+            ]
+        }
+    }
+
+    #[test]
+    fn typing_and_choosing_update_state() {
+        let harness = TestHarness::<Form>::new(());
+        harness.type_into(&harness.find_text_input(""), "Sam");
+        harness.choose(&harness.find_select("Green"), 1);
+        harness.find_label_by_text("Sam Some(1)");
+        harness.find_text_input("Sam");
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::Backend;
+
+/// Repeating timers fire at most this often, so a zero period can't keep `fire_timers` from returning
+const MIN_PERIOD: Duration = Duration::from_millis(1);
+
+struct Timer {
+    /// How often the timer fires after the first time, if it repeats
+    period: Option<Duration>,
+    fire: Rc<dyn Fn()>,
+}
+
+thread_local! {
+    /// Running timers ordered by when they next fire, ties are broken by the order they were started in
+    static TIMERS: RefCell<BTreeMap<(Instant, usize), Timer>> = const { RefCell::new(BTreeMap::new()) };
+    /// The earliest time the backend has been asked to call `fire_timers` at
+    static WAKE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Stops the timer it was returned for when dropped
+pub(crate) struct TimerHandle(usize);
+
+impl Drop for TimerHandle {
+    fn drop(&mut self) {
+        let id = self.0;
+        // Components can be dropped as the thread exits
+        let _ = TIMERS.try_with(|timers| timers.borrow_mut().retain(|(_, other), _| *other != id));
+    }
+}
+
+/// Calls `fire` once `delay` has passed by the backends clock, and every `period` after that if given one
+pub(crate) fn start<B: Backend>(
+    delay: Duration,
+    period: Option<Duration>,
+    fire: Rc<dyn Fn()>,
+) -> TimerHandle {
+    static NEXT_TIMER: AtomicUsize = AtomicUsize::new(0);
+    let id = NEXT_TIMER.fetch_add(1, Ordering::Relaxed);
+    let now = B::now();
+    let period = period.map(|period| period.max(MIN_PERIOD));
+    TIMERS.with(|timers| {
+        timers
+            .borrow_mut()
+            .insert((now + delay, id), Timer { period, fire })
+    });
+    wake_at::<B>(now + delay, now);
+    TimerHandle(id)
+}
+
+/// Fires every timer that is due by the backends clock in the order they are due, then asks the backend to call this again when the next one is.
+/// A repeating timer that has fallen behind fires once however many periods it missed, and next fires a period after now
+pub fn fire_timers<B: Backend>() {
+    let now = B::now();
+    WAKE.with(|wake| {
+        if wake.get().is_some_and(|wake| wake <= now) {
+            wake.set(None)
+        }
+    });
+    loop {
+        // Taken out before it fires so firing can start and stop timers
+        let due = TIMERS.with(|timers| {
+            let mut timers = timers.borrow_mut();
+            let (&(deadline, id), _) = timers
+                .first_key_value()
+                .filter(|(&(deadline, _), _)| deadline <= now)?;
+            let timer = timers.remove(&(deadline, id))?;
+            if let Some(period) = timer.period {
+                let next = if deadline + period > now {
+                    deadline + period
+                } else {
+                    now + period
+                };
+                timers.insert(
+                    (next, id),
+                    Timer {
+                        period: timer.period,
+                        fire: timer.fire.clone(),
+                    },
+                );
+            }
+            Some(timer.fire)
+        });
+        let Some(fire) = due else {
+            break;
+        };
+        fire()
+    }
+    let next = TIMERS.with(|timers| {
+        timers
+            .borrow()
+            .first_key_value()
+            .map(|(&(deadline, _), _)| deadline)
+    });
+    if let Some(next) = next {
+        wake_at::<B>(next, now)
+    }
+}
+
+/// Asks the backend to fire timers at `deadline` unless it will already have by then
+fn wake_at<B: Backend>(deadline: Instant, now: Instant) {
+    let sooner = WAKE.with(|wake| {
+        let sooner = wake.get().is_none_or(|wake| deadline < wake);
+        if sooner {
+            wake.set(Some(deadline))
+        }
+        sooner
+    });
+    if sooner {
+        B::schedule_timer(deadline - now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClickHandler, Component, Subscription, TestHarness, VButton, VLabel, VNode};
+
+    #[derive(PartialEq, Clone)]
+    struct Stopwatch;
+
+    #[derive(PartialEq, Clone, Default)]
+    struct StopwatchState {
+        running: bool,
+        ticks: u32,
+        timeouts: u32,
+    }
+
+    #[derive(PartialEq, Clone, Debug)]
+    enum StopwatchMessage {
+        Tick,
+        Timeout,
+    }
+
+    impl Component for Stopwatch {
+        type Props = Duration;
+        type State = StopwatchState;
+        type Message = StopwatchMessage;
+        fn render(_props: &Self::Props, state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(
+                        |_, state: &mut StopwatchState| state.running = !state.running,
+                    )),
+                    text: "Start or stop".to_string(),
+                }),
+                VNode::Label(VLabel {
+                    text: format!("{} ticks {} timeouts", state.ticks, state.timeouts),
+                }),
+            ]
+        }
+        fn on_message(
+            message: &StopwatchMessage,
+            _props: &Self::Props,
+            state: &mut Self::State,
+        ) -> bool {
+            match message {
+                StopwatchMessage::Tick => state.ticks += 1,
+                StopwatchMessage::Timeout => state.timeouts += 1,
+            }
+            true
+        }
+        fn subscriptions(
+            period: &Self::Props,
+            state: &Self::State,
+        ) -> Vec<Subscription<StopwatchMessage>> {
+            let mut subscriptions = vec![Subscription::after(
+                Duration::from_secs(10),
+                StopwatchMessage::Timeout,
+            )];
+            if state.running {
+                subscriptions.push(Subscription::every(*period, StopwatchMessage::Tick));
+            }
+            subscriptions
+        }
+    }
+
+    #[test]
+    fn starts_and_stops_timers_as_subscriptions_change() {
+        let harness = TestHarness::<Stopwatch>::new(Duration::from_secs(1));
+        harness.advance(Duration::from_secs(2));
+        harness.find_label_by_text("0 ticks 0 timeouts");
+        harness.click(&harness.find_button("Start or stop"));
+        for _ in 0..3 {
+            harness.advance(Duration::from_secs(1));
+        }
+        harness.find_label_by_text("3 ticks 0 timeouts");
+        harness.click(&harness.find_button("Start or stop"));
+        harness.advance(Duration::from_secs(2));
+        harness.find_label_by_text("3 ticks 0 timeouts");
+        harness.advance(Duration::from_secs(30));
+        harness.find_label_by_text("3 ticks 1 timeouts");
+    }
+
+    #[test]
+    fn coalesces_missed_ticks() {
+        let harness = TestHarness::<Stopwatch>::new(Duration::from_secs(1));
+        harness.click(&harness.find_button("Start or stop"));
+        harness.advance(Duration::from_millis(5500));
+        harness.find_label_by_text("1 ticks 0 timeouts");
+        // The next tick is a period after the late one rather than on the old schedule
+        harness.advance(Duration::from_millis(900));
+        harness.find_label_by_text("1 ticks 0 timeouts");
+        harness.advance(Duration::from_millis(100));
+        harness.find_label_by_text("2 ticks 0 timeouts");
+    }
+
+    #[test]
+    fn ticks_with_a_zero_period() {
+        let harness = TestHarness::<Stopwatch>::new(Duration::ZERO);
+        harness.click(&harness.find_button("Start or stop"));
+        harness.advance(Duration::from_secs(1));
+        harness.find_label_by_text("1 ticks 0 timeouts");
+    }
+}
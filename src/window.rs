@@ -0,0 +1,179 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{ClickHandler, Component, KeyValue, VComponent};
+
+/// A top level window a component keeps open for as long as `Component::windows` returns it.
+/// Windows are matched up with the ones returned after the previous render by their key, new ones are opened, ones that are gone are closed and the rest are updated in place.
+/// The content is a component of its own, rendered into the window the same way a `VNode::Custom` is rendered into a view
+pub struct VWindow<T: Component + ?Sized> {
+    pub key: KeyValue,
+    pub attributes: WindowAttributes,
+    /// Run when the user closes the window.
+    /// The window is forgotten either way, so it should stop being returned or it will open again
+    pub on_close: Option<ClickHandler<T>>,
+    pub content: VComponent,
+}
+
+/// Everything about a window other than what is in it
+#[derive(Clone, PartialEq, Debug)]
+pub struct WindowAttributes {
+    pub title: String,
+    /// The width and height of the content, changing it resizes the window
+    pub size: (f64, f64),
+    /// How small the user can make the content, any size when `None`
+    pub min_size: Option<(f64, f64)>,
+    /// Whether the window has a close button
+    pub closable: bool,
+}
+
+impl<T: Component + ?Sized> VWindow<T> {
+    /// A closable 640 by 480 window
+    pub fn new(
+        key: impl Hash + Eq + Debug + 'static,
+        title: impl Into<String>,
+        content: VComponent,
+    ) -> Self {
+        Self {
+            key: KeyValue::new(key),
+            attributes: WindowAttributes {
+                title: title.into(),
+                size: (640., 480.),
+                min_size: None,
+                closable: true,
+            },
+            on_close: None,
+            content,
+        }
+    }
+
+    pub fn size(mut self, width: f64, height: f64) -> Self {
+        self.attributes.size = (width, height);
+        self
+    }
+
+    pub fn min_size(mut self, width: f64, height: f64) -> Self {
+        self.attributes.min_size = Some((width, height));
+        self
+    }
+
+    pub fn closable(mut self, closable: bool) -> Self {
+        self.attributes.closable = closable;
+        self
+    }
+
+    pub fn on_close(mut self, handler: ClickHandler<T>) -> Self {
+        self.on_close = Some(handler);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ClickHandler, Component, HeadlessBackend, HeadlessEvent, TestHarness, VButton, VComponent,
+        VLabel, VNode, VWindow,
+    };
+
+    #[derive(PartialEq, Clone)]
+    struct Greeting;
+
+    impl Component for Greeting {
+        type Props = String;
+        type State = ();
+        fn render(props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![VNode::Label(VLabel {
+                text: format!("Hello {props}"),
+            })]
+        }
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct Editor;
+
+    #[derive(PartialEq, Clone, Default)]
+    struct EditorState {
+        inspecting: bool,
+        selection: usize,
+    }
+
+    impl Component for Editor {
+        type Props = ();
+        type State = EditorState;
+        fn render(_props: &Self::Props, _state: &Self::State) -> Vec<VNode<Self>> {
+            vec![
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, state: &mut EditorState| {
+                        state.inspecting = !state.inspecting
+                    })),
+                    text: "Inspect".to_string(),
+                }),
+                VNode::Button(VButton {
+                    click: Some(ClickHandler::<Self>::new(|_, state: &mut EditorState| {
+                        state.selection += 1
+                    })),
+                    text: "Next".to_string(),
+                }),
+            ]
+        }
+        fn windows(_props: &Self::Props, state: &Self::State) -> Vec<VWindow<Self>> {
+            if !state.inspecting {
+                return Vec::new();
+            }
+            vec![VWindow::new(
+                "inspector",
+                format!("Inspector {}", state.selection),
+                VComponent::new::<Greeting>(state.selection.to_string()),
+            )
+            .min_size(200., 100.)
+            .on_close(ClickHandler::<Self>::new(|_, state| {
+                state.inspecting = false
+            }))]
+        }
+    }
+
+    #[test]
+    fn opens_updates_and_closes_windows_as_state_changes() {
+        let harness = TestHarness::<Editor>::new(());
+        assert!(HeadlessBackend::windows().is_empty());
+        harness.click(&harness.find_button("Inspect"));
+        let window = harness.find_window("Inspector 0");
+        harness.find_label_by_text("Hello 0");
+        HeadlessBackend::take_events();
+        harness.click(&harness.find_button("Next"));
+        // Kept open under the same key with its title and content updated in place
+        assert_eq!(harness.find_window("Inspector 1").id(), window.id());
+        harness.find_label_by_text("Hello 1");
+        let events = HeadlessBackend::take_events();
+        assert!(events.contains(&HeadlessEvent::UpdateWindow {
+            window: window.id(),
+            attributes: WindowAttributes {
+                title: "Inspector 1".to_string(),
+                size: (640., 480.),
+                min_size: Some((200., 100.)),
+                closable: true,
+            },
+        }));
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, HeadlessEvent::OpenWindow { .. })));
+        harness.click(&harness.find_button("Inspect"));
+        assert!(
+            HeadlessBackend::take_events().contains(&HeadlessEvent::CloseWindow {
+                window: window.id()
+            })
+        );
+        assert!(HeadlessBackend::windows().is_empty());
+    }
+
+    #[test]
+    fn runs_on_close_when_the_user_closes_a_window() {
+        let harness = TestHarness::<Editor>::new(());
+        harness.click(&harness.find_button("Inspect"));
+        harness.close_window(&harness.find_window("Inspector 0"));
+        assert!(HeadlessBackend::windows().is_empty());
+        // `on_close` cleared the flag so this opens it again rather than toggling it off
+        harness.click(&harness.find_button("Inspect"));
+        harness.find_window("Inspector 0");
+    }
+}